@@ -0,0 +1,229 @@
+//! Achievements are defined in `assets/main.achievements.ron`. Unlocks are saved and shown
+//! in a toast as they happen.
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use crate::{
+    components::{Character, DespawnAfter, Enemy, Player},
+    events::{DamageApplied, Die, EnemyAttackOutcome},
+    game_state::GameState,
+    layout::{Anchor, Anchored},
+    loading::{self, Fonts},
+    replay,
+    resources::Score,
+    save::Saves,
+    types::DamageType,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+pub struct Plugin;
+
+const SAVE_KEY: &str = "achievements";
+const TOAST_DURATION: Duration = Duration::from_secs(3);
+
+#[derive(Clone, Debug, Deserialize, TypeUuid)]
+#[uuid = "3c1f5a7e-9b42-4d8a-b6e0-52f7d4a1c9e8"]
+pub struct Achievements {
+    pub achievements: Vec<Achievement>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Achievement {
+    /// Saved when unlocked, so don't change it once released.
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub condition: Condition,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub enum Condition {
+    /// Kill this character without taking damage during the fight.
+    FlawlessKill(Character),
+    /// Kill this character with only this type of damage.
+    KillUsingOnly {
+        character: Character,
+        damage_type: DamageType,
+    },
+    /// Defend this many enemy attacks in a row.
+    DefendStreak(u32),
+    /// Reach this many hits in a combo.
+    Combo(u32),
+}
+
+/// Ids of unlocked achievements.
+#[derive(Default, Deserialize, Serialize)]
+pub struct Unlocked {
+    pub ids: Vec<String>,
+}
+
+/// What's happened so far in the current fight.
+#[derive(Default)]
+struct Progress {
+    took_damage: bool,
+    damage_types: Vec<DamageType>,
+    defend_streak: u32,
+}
+
+struct Toast;
+
+#[derive(Default)]
+struct AchievementsLoader;
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_asset::<Achievements>()
+            .init_asset_loader::<AchievementsLoader>()
+            .insert_resource(Progress::default())
+            .add_system_set(
+                SystemSet::on_enter(GameState::CreateResources)
+                    .with_system(create_resources.system()))
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(reset_progress.system()))
+            // Achievements have to be earned live.
+            .add_system_set(
+                replay::live_on_update()
+                    .with_system(evaluate.system()))
+            ;
+    }
+}
+
+impl AssetLoader for AchievementsLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let achievements: Achievements = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(achievements));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["achievements.ron"]
+    }
+}
+
+fn create_resources(
+    mut commands: Commands,
+    data: Res<loading::Data>,
+    achievements: Res<Assets<Achievements>>,
+    saves: Res<Saves>,
+) {
+    let achievements = achievements.get(&data.achievements)
+        .expect("Achievements should be loaded");
+    commands.insert_resource(achievements.clone());
+
+    let unlocked: Unlocked = saves.load(SAVE_KEY).unwrap_or_default();
+    commands.insert_resource(unlocked);
+}
+
+fn reset_progress(
+    mut progress: ResMut<Progress>,
+) {
+    *progress = Progress::default();
+}
+
+fn evaluate(
+    mut commands: Commands,
+    mut damage_applied_reader: EventReader<DamageApplied>,
+    mut die_reader: EventReader<Die>,
+    mut outcome_reader: EventReader<EnemyAttackOutcome>,
+    enemy_query: Query<&Character, With<Enemy>>,
+    player_query: Query<&Player>,
+    achievements: Res<Achievements>,
+    fonts: Res<Fonts>,
+    mut progress: ResMut<Progress>,
+    saves: Res<Saves>,
+    score: Res<Score>,
+    time: Res<Time>,
+    mut unlocked: ResMut<Unlocked>,
+) {
+    for damage_applied in damage_applied_reader.iter() {
+        let damage = &damage_applied.damage;
+        if player_query.get(damage.target).is_ok() {
+            progress.took_damage = true;
+        } else if !progress.damage_types.contains(&damage.damage_type) {
+            progress.damage_types.push(damage.damage_type.clone());
+        }
+    }
+
+    for outcome in outcome_reader.iter() {
+        progress.defend_streak =
+            if outcome.defended {
+                progress.defend_streak + 1
+            } else {
+                0
+            };
+    }
+
+    let mut killed = None;
+    for die in die_reader.iter() {
+        if let Ok(character) = enemy_query.get(die.target) {
+            killed = Some(character.clone());
+        }
+    }
+
+    let mut toasts = 0;
+    for achievement in achievements.achievements.iter() {
+        if unlocked.ids.contains(&achievement.id) {
+            continue;
+        }
+
+        let done = match &achievement.condition {
+            Condition::FlawlessKill(character) =>
+                killed.as_ref() == Some(character) && !progress.took_damage,
+            Condition::KillUsingOnly { character, damage_type } =>
+                killed.as_ref() == Some(character)
+                && progress.damage_types.iter().all(|t| t == damage_type),
+            Condition::DefendStreak(n) => progress.defend_streak >= *n,
+            Condition::Combo(n) => score.combo >= *n,
+        };
+        if !done {
+            continue;
+        }
+
+        log::info!("Achievement unlocked: {}", achievement.id);
+        unlocked.ids.push(achievement.id.clone());
+        saves.save(SAVE_KEY, &*unlocked);
+
+        commands.spawn_bundle(Text2dBundle {
+            text: Text::with_section(
+                format!("Achievement unlocked: {}\n{}", achievement.name, achievement.description),
+                TextStyle {
+                    font: fonts.fira_sans.clone(),
+                    font_size: 20.,
+                    color: Color::GOLD,
+                },
+                TextAlignment {
+                    vertical: VerticalAlign::Center,
+                    horizontal: HorizontalAlign::Center,
+                }),
+            transform: Transform {
+                translation: Vec3::new(0., 160. - 50. * toasts as f32, 30.),
+                .. Default::default()
+            },
+            .. Default::default()
+        }).insert(Toast)
+          .insert(Anchored::new(Anchor::Top))
+          .insert(DespawnAfter {
+              after: time.time_since_startup() + TOAST_DURATION,
+          });
+        toasts += 1;
+    }
+
+    // The next enemy starts a new fight.
+    if killed.is_some() {
+        progress.took_damage = false;
+        progress.damage_types.clear();
+    }
+}
@@ -2,17 +2,20 @@ use bevy::prelude::*;
 // use bevy_kira_audio::Audio;
 
 use crate::{
-    components::{Action, Health, Player},
-    events::{EnemyAttackTime, MusicTime, PlayerAttackAction, PlayerDefendAction},
-    resources::Countdown,
-    types::{DamageType},
+    components::{Action, Enemy, Health, Player},
+    events::{ActionTimed, ButtonPressed, EnemyAttackTime, MusicTime, PlayerAttackAction, PlayerDefendAction,
+             PlayerHealAction},
+    resources::{Countdown, Difficulty, Loadout, Upgrades},
     game_state::GameState,
-    loading,
+    input::PrimaryButton,
+    loading::{self, Fonts},
+    replay::{Clock, ReplayState},
+    settings::Settings,
+    types::Judgement,
 };
 use std::f64::consts::PI;
 
 struct ActionIcon {
-    #[allow(dead_code)]
     action: Action,
     /// Angle in radians for where on the spinner this icon goes.
     angle: f64,
@@ -23,24 +26,33 @@ struct ActionIcon {
 struct ActionPointer {
     /// Angle of the pointer in radians
     angle: f64,
+    /// Length of a beat in the current music.
+    beat_secs: f64,
+    /// Beats the pointer takes to turn once, 4 unless the tempo is slowed.
+    beats_per_turn: f64,
 }
 
-struct ButtonPressed;
-
 struct ActionSpinner;
 
 struct PlayerMissedThisTurn(bool);
 
+/// Turns until Heal can be used again.
+struct HealCooldown(u32);
+
 struct Icons {
     pointer: Handle<ColorMaterial>,
-    sword: Handle<ColorMaterial>,
-    shield: Handle<ColorMaterial>,
-    magic: Handle<ColorMaterial>,
-    arrow: Handle<ColorMaterial>,
-    sword_highlight: Handle<ColorMaterial>,
-    shield_highlight: Handle<ColorMaterial>,
-    magic_highlight: Handle<ColorMaterial>,
-    arrow_highlight: Handle<ColorMaterial>,
+    sword: IconMaterials,
+    shield: IconMaterials,
+    magic: IconMaterials,
+    arrow: IconMaterials,
+    double_arrow: IconMaterials,
+    ray: IconMaterials,
+    heal: IconMaterials,
+}
+
+struct IconMaterials {
+    normal: Handle<ColorMaterial>,
+    highlight: Handle<ColorMaterial>,
 }
 
 pub struct Plugin;
@@ -48,10 +60,27 @@ pub struct Plugin;
 const DEFEND_ANGLE: f64 = PI;
 const ENEMY_ATTACK_ANGLE: f64 = 160. * PI / 180.;
 const ANGLE_FUDGE_RAD: f64 = 20. * PI / 180.;
+/// Extra hit window either side of an icon for each wider window upgrade.
+const WIDER_WINDOW_RAD: f64 = 5. * PI / 180.;
+/// Icons are 90 degrees apart, so keep windows from overlapping.
+const MAX_ANGLE_FUDGE_RAD: f64 = 40. * PI / 180.;
+/// Enemies attack once a turn, so healing can't keep up with a player who never defends.
+const HEAL_COOLDOWN_TURNS: u32 = 4;
+
+/// Colorblind marks sit at the bottom right of their icon.
+const MARK_OFFSET: f32 = 28.;
+
+/// Angle and position of each slot in `Loadout::slots`.
+const SLOTS: [(f64, f32, f32); 4] = [
+    (0.,       -200.,  100.),
+    (PI * 0.5, -300.,    0.),
+    (PI,       -200., -100.),
+    (PI * 1.5, -100.,    0.),
+];
 
 impl bevy::app::Plugin for Plugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_event::<ButtonPressed>()
+        app
             .add_system_set(
                 SystemSet::on_enter(GameState::CreateResources)
                     .with_system(create_resources.system()))
@@ -61,8 +90,10 @@ impl bevy::app::Plugin for Plugin {
             .add_system_set(
                 SystemSet::on_update(GameState::Playing)
                    .with_system(spin_action_pointer.system())
-                   .with_system(keyboard_input.system())
-                   .with_system(choose_action.system())
+                   .with_system(keyboard_input.system()
+                                .label("button_input")
+                                .after("replay_clock"))
+                   .with_system(choose_action.system().after("button_input"))
             );
     }
 }
@@ -72,97 +103,104 @@ fn create_resources(
     texture_assets: Res<loading::TextureAssets>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
+    let mut icon = |normal: &Handle<Texture>, highlight: &Handle<Texture>, color: Color| {
+        IconMaterials {
+            normal: materials.add(ColorMaterial::modulated_texture(normal.clone(), color)),
+            highlight: materials.add(ColorMaterial::modulated_texture(highlight.clone(), color)),
+        }
+    };
+
+    let sword = icon(&texture_assets.icon_sword, &texture_assets.icon_sword_highlight,
+                     Color::WHITE);
+    let shield = icon(&texture_assets.icon_shield, &texture_assets.icon_shield_highlight,
+                      Color::WHITE);
+    let magic = icon(&texture_assets.icon_magic, &texture_assets.icon_magic_highlight,
+                     Color::WHITE);
+    let arrow = icon(&texture_assets.icon_arrow, &texture_assets.icon_arrow_highlight,
+                     Color::WHITE);
+    let double_arrow = icon(&texture_assets.icon_arrow, &texture_assets.icon_arrow_highlight,
+                            Color::GOLD);
+    let ray = icon(&texture_assets.icon_magic, &texture_assets.icon_magic_highlight,
+                   Color::ORANGE_RED);
+    let heal = icon(&texture_assets.icon_magic, &texture_assets.icon_magic_highlight,
+                    Color::LIME_GREEN);
+
     commands.insert_resource(Icons {
         pointer: materials.add(texture_assets.icon_pointer.clone().into()),
-        sword: materials.add(texture_assets.icon_sword.clone().into()),
-        shield: materials.add(texture_assets.icon_shield.clone().into()),
-        magic: materials.add(texture_assets.icon_magic.clone().into()),
-        arrow: materials.add(texture_assets.icon_arrow.clone().into()),
-        sword_highlight: materials.add(texture_assets.icon_sword_highlight.clone().into()),
-        shield_highlight: materials.add(texture_assets.icon_shield_highlight.clone().into()),
-        magic_highlight: materials.add(texture_assets.icon_magic_highlight.clone().into()),
-        arrow_highlight: materials.add(texture_assets.icon_arrow_highlight.clone().into()),
+        sword,
+        shield,
+        magic,
+        arrow,
+        double_arrow,
+        ray,
+        heal,
     });
 }
 
 fn spawn_entities(
     mut commands: Commands,
     existing_query: Query<Entity, With<ActionSpinner>>,
+    fonts: Res<Fonts>,
     icons: Res<Icons>,
+    loadout: Res<Loadout>,
+    settings: Res<Settings>,
 ) {
     for ent in existing_query.iter() {
         commands.entity(ent).despawn();
     }
 
     commands.insert_resource(PlayerMissedThisTurn(false));
+    commands.insert_resource(HealCooldown(0));
 
-    commands.spawn_bundle(SpriteBundle {
-        material: icons.sword.clone(),
-        transform: Transform {
-            translation: Vec3::new(-200., 100., 0.),
-            scale: Vec3::ONE * 0.3,
-            .. Default::default()
-        },
-        .. Default::default()
-    })
-        .insert(ActionIcon {
-            action: Action::AttackSword,
-            angle: 0.,
-            normal_material: icons.sword.clone(),
-            highlight_material: icons.sword_highlight.clone(),
-        })
-        .insert(ActionSpinner);
-
-    commands.spawn_bundle(SpriteBundle {
-        material: icons.shield.clone(),
-        transform: Transform {
-            translation: Vec3::new(-200., -100., 0.),
-            scale: Vec3::ONE * 0.3,
-            .. Default::default()
-        },
-        .. Default::default()
-    })
-        .insert(ActionIcon {
-            action: Action::Defend,
-            angle: PI,
-            normal_material: icons.shield.clone(),
-            highlight_material: icons.shield_highlight.clone(),
-        })
-        .insert(ActionSpinner);
+    for (action, &(angle, x, y)) in loadout.slots.iter().zip(SLOTS.iter()) {
+        let (materials, scale) = match action {
+            Action::AttackArrow => (&icons.arrow, 0.5),
+            Action::AttackDoubleArrow => (&icons.double_arrow, 0.5),
+            Action::AttackMagic => (&icons.magic, 0.4),
+            Action::AttackRay => (&icons.ray, 0.4),
+            Action::AttackSword => (&icons.sword, 0.3),
+            Action::Defend => (&icons.shield, 0.3),
+            Action::Heal => (&icons.heal, 0.4),
+        };
 
-    commands.spawn_bundle(SpriteBundle {
-        material: icons.magic.clone(),
-        transform: Transform {
-            translation: Vec3::new(-300., 0., 0.),
-            scale: Vec3::ONE * 0.4,
+        commands.spawn_bundle(SpriteBundle {
+            material: materials.normal.clone(),
+            transform: Transform {
+                translation: Vec3::new(x, y, 0.),
+                scale: Vec3::ONE * scale,
+                .. Default::default()
+            },
             .. Default::default()
-        },
-        .. Default::default()
-    })
-        .insert(ActionIcon {
-            action: Action::AttackMagic,
-            angle: PI * 0.5,
-            normal_material: icons.magic.clone(),
-            highlight_material: icons.magic_highlight.clone(),
         })
-        .insert(ActionSpinner);
+            .insert(ActionIcon {
+                action: action.clone(),
+                angle,
+                normal_material: materials.normal.clone(),
+                highlight_material: materials.highlight.clone(),
+            })
+            .insert(ActionSpinner);
 
-    commands.spawn_bundle(SpriteBundle {
-        material: icons.arrow.clone(),
-        transform: Transform {
-            translation: Vec3::new(-100., 0., 0.),
-            scale: Vec3::ONE * 0.5,
-            .. Default::default()
-        },
-        .. Default::default()
-    })
-        .insert(ActionIcon {
-            action: Action::AttackArrow,
-            angle: PI * 1.5,
-            normal_material: icons.arrow.clone(),
-            highlight_material: icons.arrow_highlight.clone(),
-        })
-        .insert(ActionSpinner);
+        if let Some(mark) = action.mark().filter(|_| settings.accessibility.colorblind_icons) {
+            commands.spawn_bundle(Text2dBundle {
+                text: Text::with_section(
+                    mark,
+                    TextStyle {
+                        font: fonts.fira_sans.clone(),
+                        font_size: 24.,
+                        color: Color::WHITE,
+                    },
+                    TextAlignment {
+                        vertical: VerticalAlign::Center,
+                        horizontal: HorizontalAlign::Center,
+                    }),
+                transform: Transform {
+                    translation: Vec3::new(x + MARK_OFFSET, y - MARK_OFFSET, 1.),
+                    .. Default::default()
+                },
+                .. Default::default()
+            }).insert(ActionSpinner);
+        }
+    }
 
     commands.spawn_bundle(SpriteBundle {
         sprite: Sprite::new(Vec2::new(5., 40.)),
@@ -174,6 +212,8 @@ fn spawn_entities(
         .. Default::default()
     }).insert(ActionPointer {
         angle: 0.,
+        beat_secs: 0.,
+        beats_per_turn: 4.,
     }).insert(ActionSpinner);
 }
 
@@ -183,21 +223,30 @@ fn spin_action_pointer(
     mut pointer_pos: Query<(&mut ActionPointer, &mut Transform)>,
     mut icons_query: Query<(&ActionIcon, &mut Handle<ColorMaterial>)>,
     countdown: Res<Countdown>,
+    difficulty: Res<Difficulty>,
+    replay_state: Res<ReplayState>,
+    settings: Res<Settings>,
+    upgrades: Res<Upgrades>,
+    mut heal_cooldown: ResMut<HealCooldown>,
     mut missed_this_turn: ResMut<PlayerMissedThisTurn>,
 ) {
     for (mut ap, mut transform) in pointer_pos.single_mut() {
         let mut icons: Vec<(&ActionIcon, Mut<Handle<ColorMaterial>>)> =
             icons_query.iter_mut().collect();
 
+        let beats_per_turn = replay_state.accessibility(&settings).beats_per_turn();
         let music_time = music_time_reader.iter().last();
         let old_angle = ap.angle;
         let new_angle =
-            music_time.map(|mt| (PI - mt.beat_in_bar * (1./4.) * 2. * PI).rem_euclid(2. * PI))
+            music_time.map(|mt| pointer_angle(mt.song_beat, beats_per_turn))
                       .unwrap_or(old_angle);
 
         for (i_icon, i_mat) in icons.iter_mut() {
+            let cooling_down = i_icon.action == Action::Heal && heal_cooldown.0 > 0;
             **i_mat =
-                if in_angle_range(new_angle, i_icon.angle) {
+                if !cooling_down &&
+                    in_angle_range(new_angle, i_icon.angle,
+                                   hit_window(&i_icon.action, &upgrades, *difficulty)) {
                     i_icon.highlight_material.clone()
                 } else {
                     i_icon.normal_material.clone()
@@ -206,6 +255,7 @@ fn spin_action_pointer(
 
         if is_angle_hit(old_angle, new_angle, DEFEND_ANGLE) {
             missed_this_turn.0 = false;
+            heal_cooldown.0 = heal_cooldown.0.saturating_sub(1);
         }
 
         if *countdown == Countdown::Disabled &&
@@ -214,15 +264,52 @@ fn spin_action_pointer(
             enemy_attack_time_writer.send(EnemyAttackTime);
         }
 
+        if let Some(mt) = music_time {
+            ap.beat_secs = mt.beat_secs;
+        }
+        ap.beats_per_turn = beats_per_turn;
         ap.angle = new_angle.rem_euclid(2. * PI);
         transform.rotation = Quat::from_rotation_z(ap.angle as f32);
         trace!("spin_action_pointer: angle deg={}", ap.angle*180./PI);
     }
 }
 
-fn in_angle_range(angle: f64, target_angle: f64) -> bool {
-    (angle - target_angle).abs() < ANGLE_FUDGE_RAD
-        || (angle - 2. * PI - target_angle).abs() < ANGLE_FUDGE_RAD
+/// Angle of the pointer at a `MusicTime::song_beat`.
+pub fn pointer_angle(song_beat: f64, beats_per_turn: f64) -> f64 {
+    (PI - song_beat.rem_euclid(beats_per_turn) / beats_per_turn * 2. * PI).rem_euclid(2. * PI)
+}
+
+fn in_angle_range(angle: f64, target_angle: f64, fudge: f64) -> bool {
+    (angle - target_angle).abs() < fudge
+        || (angle - 2. * PI - target_angle).abs() < fudge
+}
+
+/// Angle from `target_angle` to `angle` in the range -PI to PI.
+fn angle_diff(angle: f64, target_angle: f64) -> f64 {
+    let diff = (angle - target_angle).rem_euclid(2. * PI);
+    if diff > PI {
+        diff - 2. * PI
+    } else {
+        diff
+    }
+}
+
+/// Half width in radians of the hit window around an action's icon.
+fn hit_window(action: &Action, upgrades: &Upgrades, difficulty: Difficulty) -> f64 {
+    (ANGLE_FUDGE_RAD * difficulty.hit_window_scale()
+     + WIDER_WINDOW_RAD * upgrades.wider_window_count(action) as f64)
+        .min(MAX_ANGLE_FUDGE_RAD)
+}
+
+/// The icon whose hit window the pointer is in, if any.
+fn icon_at<'a>(
+    angle: f64,
+    mut icons: impl Iterator<Item = &'a ActionIcon>,
+    upgrades: &Upgrades,
+    difficulty: Difficulty,
+) -> Option<&'a ActionIcon> {
+    icons.find(|icon| in_angle_range(angle, icon.angle,
+                                     hit_window(&icon.action, upgrades, difficulty)))
 }
 
 fn is_angle_hit(old_angle: f64, new_angle: f64, target_angle: f64) -> bool {
@@ -237,14 +324,39 @@ fn is_angle_hit(old_angle: f64, new_angle: f64, target_angle: f64) -> bool {
 
 fn keyboard_input(
     mut button_writer: EventWriter<ButtonPressed>,
+    mut button: PrimaryButton,
+    pointer: Query<&ActionPointer>,
+    icons: Query<&ActionIcon>,
+    clock: Res<Clock>,
     countdown: Res<Countdown>,
-    kb: Res<Input<KeyCode>>,
+    difficulty: Res<Difficulty>,
+    replay_state: Res<ReplayState>,
+    settings: Res<Settings>,
+    upgrades: Res<Upgrades>,
+    mut armed: Local<bool>,
 ) {
-    if *countdown == Countdown::Disabled &&
-        kb.just_pressed(KeyCode::Space)
-    {
+    // Take the press even when it's ignored, so it isn't seen once the countdown ends.
+    let pressed = button.take();
+    if *countdown != Countdown::Disabled || replay_state.is_playing() {
+        *armed = false;
+        return;
+    }
+
+    if settings.accessibility.hold_to_act {
+        // A press arms the button, then the action is taken when the pointer reaches an icon,
+        // as long as the button is still held.
+        *armed = (*armed || pressed) && button.held();
+        let on_icon = pointer.single().ok()
+            .and_then(|ptr| icon_at(ptr.angle, icons.iter(), &upgrades, *difficulty))
+            .is_some();
+        if *armed && on_icon {
+            debug!("keyboard_input: emit ButtonPressed while held");
+            button_writer.send(ButtonPressed { song_beat: clock.song_beat });
+            *armed = false;
+        }
+    } else if pressed {
         debug!("keyboard_input: emit ButtonPressed");
-        button_writer.send(ButtonPressed);
+        button_writer.send(ButtonPressed { song_beat: clock.song_beat });
     }
 }
 
@@ -252,43 +364,88 @@ fn choose_action(
     mut button_reader: EventReader<ButtonPressed>,
     mut attack_writer: EventWriter<PlayerAttackAction>,
     mut defend_writer: EventWriter<PlayerDefendAction>,
+    mut heal_writer: EventWriter<PlayerHealAction>,
+    mut timed_writer: EventWriter<ActionTimed>,
     pointer: Query<&ActionPointer>,
+    icons: Query<&ActionIcon>,
     player: Query<&Health, With<Player>>,
+    enemy: Query<&Health, With<Enemy>>,
+    difficulty: Res<Difficulty>,
+    loadout: Res<Loadout>,
+    replay_state: Res<ReplayState>,
+    settings: Res<Settings>,
+    upgrades: Res<Upgrades>,
+    mut heal_cooldown: ResMut<HealCooldown>,
     mut missed_this_turn: ResMut<PlayerMissedThisTurn>,
 ) {
-    if button_reader.iter().next().is_some() {
+    if let Some(press) = button_reader.iter().next() {
         // Button was pressed
 
         match player.single() {
             Ok(health) if health.current > 0 => {
                 let ptr = pointer.single().unwrap();
-                let deg = ptr.angle * 180. / PI;
-                if deg >= 160. && deg <= 200. {
-                    debug!("choose_action: emit PlayerDefendAction");
-                    defend_writer.send(PlayerDefendAction);
-                } else if !missed_this_turn.0 {
-                    if deg >= 0. && deg <= 20. || deg >= 340. {
-                        let attack = PlayerAttackAction {
-                            damage_type: DamageType::Sword,
-                        };
-                        debug!("choose_action: emit {:?}", attack);
-                        attack_writer.send(attack);
-                    } else if deg >= 70. && deg <= 110. {
-                        let attack = PlayerAttackAction {
-                            damage_type: DamageType::Magic,
-                        };
-                        debug!("choose_action: emit {:?}", attack);
-                        attack_writer.send(attack);
-                    } else if deg >= 250. && deg <= 290. {
+                let angle = pointer_angle(press.song_beat, ptr.beats_per_turn);
+                let mut action = icon_at(angle, icons.iter(), &upgrades, *difficulty)
+                    .map(|icon| &icon.action);
+
+                if replay_state.accessibility(&settings).auto_attack {
+                    if let (Some(chosen), Ok(enemy_health)) = (action, enemy.single()) {
+                        if chosen.damage_type().is_some() {
+                            action = loadout.slots.iter()
+                                .find(|a| a.damage_type()
+                                           .map(|t| enemy_health.vulnerable_to.contains(&t))
+                                           .unwrap_or(false))
+                                .or(action);
+                        }
+                    }
+                }
+
+                let nearest = icons.iter()
+                    .min_by(|a, b| angle_diff(angle, a.angle).abs()
+                                   .partial_cmp(&angle_diff(angle, b.angle).abs())
+                                   .unwrap());
+                let mut judgement = None;
+                if let Some(icon) = nearest {
+                    // The pointer turns with decreasing angle, so a positive difference
+                    // means it hasn't reached the icon yet.
+                    let turns = angle_diff(angle, icon.angle) / (2. * PI);
+                    let timed = ActionTimed {
+                        action: icon.action.clone(),
+                        hit: action.is_some(),
+                        offset_secs: -turns * ptr.beats_per_turn * ptr.beat_secs,
+                    };
+                    judgement = Some(timed.judgement());
+                    timed_writer.send(timed);
+                }
+
+                match action {
+                    Some(Action::Defend) => {
+                        debug!("choose_action: emit PlayerDefendAction");
+                        defend_writer.send(PlayerDefendAction);
+                    },
+                    _ if missed_this_turn.0 => {},
+                    Some(Action::Heal) if heal_cooldown.0 > 0 => {},
+                    Some(Action::Heal) => {
+                        debug!("choose_action: emit PlayerHealAction");
+                        heal_writer.send(PlayerHealAction);
+                        heal_cooldown.0 = HEAL_COOLDOWN_TURNS;
+                    },
+                    Some(action) => {
                         let attack = PlayerAttackAction {
-                            damage_type: DamageType::Arrow,
+                            damage_type: action.damage_type().unwrap(),
+                            hp: match action {
+                                Action::AttackDoubleArrow => 2,
+                                _ => 1,
+                            },
+                            crit: judgement == Some(Judgement::Perfect),
                         };
                         debug!("choose_action: emit {:?}", attack);
                         attack_writer.send(attack);
-                    } else {
+                    },
+                    None => {
                         // Missed all actions.
                         missed_this_turn.0 = true;
-                    }
+                    },
                 }
             },
             _ => {},
@@ -0,0 +1,223 @@
+use bevy::prelude::*;
+use crate::{
+    components::{AttackType, Character, Enemy, Health},
+    events::Die,
+    game_state::GameState,
+    input::PrimaryButton,
+    layout::{Anchor, Anchored},
+    loading::Fonts,
+    replay,
+    save::Saves,
+    settings::Settings,
+    types::{DamageType, Hp},
+};
+use serde::{Deserialize, Serialize};
+
+/// Records each kind of enemy met, saved across runs and shown on the
+/// bestiary screen. Replays don't unlock anything.
+pub struct Plugin;
+
+const SAVE_KEY: &str = "bestiary";
+
+/// In the order they're listed.
+const CHARACTERS: [Character; 4] = [
+    Character::Archer,
+    Character::Knight,
+    Character::Mage,
+    Character::Boss,
+];
+
+/// Missing fields take their default value, so older bestiary files still load.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Bestiary {
+    pub entries: Vec<Entry>,
+}
+
+/// Copied from the enemy each time it's met.
+#[derive(Deserialize, Serialize)]
+pub struct Entry {
+    pub character: Character,
+    pub hp: Hp,
+    pub weaknesses: Vec<DamageType>,
+    pub attack: DamageType,
+    pub defeated: u32,
+}
+
+struct BestiaryEntity;
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_system_set(
+                SystemSet::on_enter(GameState::CreateResources)
+                    .with_system(create_resources.system()))
+            .add_system_set(
+                replay::live_on_update()
+                    .with_system(meet.system())
+                    .with_system(die.system()))
+            .add_system_set(
+                SystemSet::on_enter(GameState::Bestiary)
+                    .with_system(on_enter.system()))
+            .add_system_set(
+                SystemSet::on_update(GameState::Bestiary)
+                    .with_system(keyboard_input.system()))
+            .add_system_set(
+                SystemSet::on_exit(GameState::Bestiary)
+                    .with_system(cleanup.system()))
+            ;
+    }
+}
+
+impl Bestiary {
+    fn entry(&self, character: &Character) -> Option<&Entry> {
+        self.entries.iter().find(|e| &e.character == character)
+    }
+}
+
+impl Entry {
+    fn text(&self) -> String {
+        let weaknesses: Vec<String> = self.weaknesses.iter()
+            .map(|t| format!("{:?}", t))
+            .collect();
+        format!("{:?}   HP {}   Attacks with {:?}\nWeak to {}   Defeated {}",
+                self.character, self.hp, self.attack, weaknesses.join(", "), self.defeated)
+    }
+}
+
+fn create_resources(
+    mut commands: Commands,
+    saves: Res<Saves>,
+) {
+    let bestiary: Bestiary = saves.load(SAVE_KEY).unwrap_or_default();
+    commands.insert_resource(bestiary);
+}
+
+fn meet(
+    query: Query<(&Character, &Health, &AttackType), Added<Enemy>>,
+    mut bestiary: ResMut<Bestiary>,
+    saves: Res<Saves>,
+) {
+    for (character, health, attack_type) in query.iter() {
+        let met = Entry {
+            character: character.clone(),
+            hp: health.max,
+            weaknesses: health.vulnerable_to.clone(),
+            attack: attack_type.damage_type.clone(),
+            defeated: 0,
+        };
+        // Stats can differ between levels and difficulties, so keep the latest.
+        match bestiary.entries.iter_mut().find(|e| e.character == met.character) {
+            Some(entry) => *entry = Entry { defeated: entry.defeated, .. met },
+            None => bestiary.entries.push(met),
+        }
+        saves.save(SAVE_KEY, &*bestiary);
+    }
+}
+
+fn die(
+    mut die_reader: EventReader<Die>,
+    enemy_query: Query<&Character, With<Enemy>>,
+    mut bestiary: ResMut<Bestiary>,
+    saves: Res<Saves>,
+) {
+    for die in die_reader.iter() {
+        let character = match enemy_query.get(die.target) {
+            Ok(c) => c,
+            Err(_) => continue,
+        };
+        if let Some(entry) = bestiary.entries.iter_mut().find(|e| &e.character == character) {
+            entry.defeated += 1;
+            saves.save(SAVE_KEY, &*bestiary);
+        }
+    }
+}
+
+fn on_enter(
+    mut commands: Commands,
+    bestiary: Res<Bestiary>,
+    fonts: Res<Fonts>,
+    settings: Res<Settings>,
+) {
+    let alignment = TextAlignment {
+        vertical: VerticalAlign::Center,
+        horizontal: HorizontalAlign::Center,
+    };
+
+    commands.spawn_bundle(Text2dBundle {
+        text: Text::with_section(
+            "Bestiary",
+            TextStyle {
+                font: fonts.fiendish.clone(),
+                font_size: 60.,
+                color: Color::rgb(242./255., 0., 48./255.),
+            },
+            alignment),
+        transform: Transform {
+            translation: Vec3::new(0., 240., 10.),
+            .. Default::default()
+        },
+        .. Default::default()
+    }).insert(Anchored::new(Anchor::Top))
+      .insert(BestiaryEntity);
+
+    let lines: Vec<String> = CHARACTERS.iter()
+        .map(|character| match bestiary.entry(character) {
+            Some(entry) => entry.text(),
+            None => "???\nNot met yet".to_owned(),
+        })
+        .collect();
+    commands.spawn_bundle(Text2dBundle {
+        text: Text::with_section(
+            lines.join("\n\n"),
+            TextStyle {
+                font: fonts.fira_sans.clone(),
+                font_size: 20.,
+                color: Color::WHITE,
+            },
+            TextAlignment {
+                vertical: VerticalAlign::Top,
+                horizontal: HorizontalAlign::Center,
+            }),
+        transform: Transform {
+            translation: Vec3::new(0., 180., 10.),
+            .. Default::default()
+        },
+        .. Default::default()
+    }).insert(BestiaryEntity);
+
+    commands.spawn_bundle(Text2dBundle {
+        text: Text::with_section(
+            settings.keys.prompt("return"),
+            TextStyle {
+                font: fonts.fira_sans.clone(),
+                font_size: 20.,
+                color: Color::WHITE,
+            },
+            alignment),
+        transform: Transform {
+            translation: Vec3::new(0., -250., 10.),
+            .. Default::default()
+        },
+        .. Default::default()
+    }).insert(Anchored::new(Anchor::Bottom))
+      .insert(BestiaryEntity);
+}
+
+fn keyboard_input(
+    mut button: PrimaryButton,
+    mut state: ResMut<State<GameState>>,
+) {
+    if button.take() {
+        state.set(GameState::Menu).unwrap();
+    }
+}
+
+fn cleanup(
+    mut commands: Commands,
+    query: Query<Entity, With<BestiaryEntity>>,
+) {
+    for ent in query.iter() {
+        commands.entity(ent).despawn();
+    }
+}
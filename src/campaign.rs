@@ -0,0 +1,169 @@
+//! The campaign is the list of stages in a run, loaded from `assets/main.campaign.ron`.
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use crate::{
+    components::Character,
+    game_state::GameState,
+    loading,
+    resources::Mode,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// Registers the campaign asset and copies the loaded campaign into a resource. A campaign
+/// that fails to parse or check fails to load, which shows the loading failed screen.
+pub struct Plugin;
+
+#[derive(Clone, Debug, Deserialize, TypeUuid)]
+#[uuid = "8a4e0b61-2f3c-4f7e-9c59-0f2d7e1b6a93"]
+pub struct Campaign {
+    pub stages: Vec<Stage>,
+    /// How many times a run may continue from a checkpoint after game over.
+    #[serde(default)]
+    pub continues: u8,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Stage {
+    /// Shown in the level box for the whole stage.
+    pub name: String,
+    /// Shown in the middle of the screen as the stage starts.
+    #[serde(default)]
+    pub intro: Option<String>,
+    pub enemies: Enemies,
+    pub song: Song,
+    pub backdrop: Backdrop,
+    /// Seconds to wait after the last enemy of the stage dies.
+    pub rest_secs: f32,
+    /// Play the boss warning as the previous stage ends.
+    #[serde(default)]
+    pub boss_intro: bool,
+    /// After game over the run can continue from the last checkpoint stage reached.
+    #[serde(default)]
+    pub checkpoint: bool,
+}
+
+/// The enemies fought one after another in a stage.
+#[derive(Clone, Debug, Deserialize)]
+pub enum Enemies {
+    Fixed(Vec<Character>),
+    Random {
+        pool: Vec<Character>,
+        count: usize,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Song {
+    Ninja,
+}
+
+impl Song {
+    pub const ALL: [Song; 1] = [Song::Ninja];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Song::Ninja => "Ninja",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+pub enum Backdrop {
+    Day,
+    Night,
+    Inferno,
+}
+
+#[derive(Default)]
+struct CampaignLoader;
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_asset::<Campaign>()
+            .init_asset_loader::<CampaignLoader>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::CreateResources)
+                    .with_system(create_resources.system()));
+    }
+}
+
+impl AssetLoader for CampaignLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let campaign: Campaign = ron::de::from_bytes(bytes)?;
+            campaign.check()?;
+            load_context.set_default_asset(LoadedAsset::new(campaign));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["campaign.ron"]
+    }
+}
+
+impl Campaign {
+    /// The song played during `stage`. Practice plays the chosen song throughout.
+    pub fn song(&self, stage: usize, mode: Mode) -> Song {
+        match mode {
+            Mode::Campaign => self.stages[stage].song,
+            Mode::Practice(song) => song,
+        }
+    }
+
+    /// Every stage must have an enemy to fight.
+    fn check(&self) -> Result<(), anyhow::Error> {
+        if self.stages.is_empty() {
+            anyhow::bail!("Campaign has no stages");
+        }
+        for stage in self.stages.iter() {
+            if stage.enemies.num_fights() == 0 {
+                anyhow::bail!("Stage '{}' has no enemies", stage.name);
+            }
+            if matches!(&stage.enemies, Enemies::Random { pool, .. } if pool.is_empty()) {
+                anyhow::bail!("Stage '{}' has an empty enemy pool", stage.name);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Enemies {
+    pub fn num_fights(&self) -> usize {
+        match self {
+            Enemies::Fixed(characters) => characters.len(),
+            Enemies::Random { count, .. } => *count,
+        }
+    }
+
+    /// The enemy for a 0 indexed fight in the stage.
+    pub fn character(&self, fight: usize, rng: &mut impl Rng) -> Character {
+        match self {
+            Enemies::Fixed(characters) => characters[fight].clone(),
+            Enemies::Random { pool, .. } =>
+                pool[rng.gen_range(0..pool.len())].clone(),
+        }
+    }
+}
+
+fn create_resources(
+    mut commands: Commands,
+    data: Res<loading::Data>,
+    campaigns: Res<Assets<Campaign>>,
+) {
+    // Loading only finishes once the campaign has loaded.
+    if let Some(campaign) = campaigns.get(&data.campaign) {
+        commands.insert_resource(campaign.clone());
+    }
+}
@@ -1,14 +1,55 @@
 //! Shared components
 
 use crate::types::{DamageType, Hp};
+use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
-#[derive(Eq, PartialEq)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub enum Action {
     AttackArrow,
+    AttackDoubleArrow,
     AttackMagic,
+    AttackRay,
     AttackSword,
     Defend,
+    Heal,
+}
+
+impl Action {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::AttackArrow => "Arrow",
+            Action::AttackDoubleArrow => "Double Arrow",
+            Action::AttackMagic => "Magic",
+            Action::AttackRay => "Ray",
+            Action::AttackSword => "Sword",
+            Action::Defend => "Defend",
+            Action::Heal => "Heal",
+        }
+    }
+
+    /// A shape telling apart icons that share a picture and differ only in colour, shown
+    /// when colorblind icons are on.
+    pub fn mark(&self) -> Option<&'static str> {
+        match self {
+            Action::AttackDoubleArrow => Some("x2"),
+            Action::AttackRay => Some("*"),
+            Action::Heal => Some("+"),
+            _ => None,
+        }
+    }
+
+    /// The type of damage done by an attack, or `None` if this isn't an attack.
+    pub fn damage_type(&self) -> Option<DamageType> {
+        match self {
+            Action::AttackArrow => Some(DamageType::Arrow),
+            Action::AttackDoubleArrow => Some(DamageType::Pierce),
+            Action::AttackMagic => Some(DamageType::Magic),
+            Action::AttackRay => Some(DamageType::Light),
+            Action::AttackSword => Some(DamageType::Sword),
+            Action::Defend | Action::Heal => None,
+        }
+    }
 }
 
 pub struct Enemy;
@@ -22,7 +63,7 @@ pub struct Health {
 
 pub struct Player;
 
-#[derive(Clone)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub enum Character {
     Archer,
     Knight,
@@ -42,6 +83,9 @@ pub struct DespawnAfter {
     pub after: Duration,
 }
 
+/// A flashing effect, held on its first frame and faded when reduced flashing is on.
+pub struct Flashing;
+
 #[derive(Debug)]
 pub struct AnimateSpriteSheet {
     pub frame_duration: Duration,
@@ -0,0 +1,213 @@
+//! Scrolling credits, read from `assets/sources.md` so the attributions the asset licences
+//! require are shown in the game as well as the repository.
+//!
+//! Only the bits of markdown the file uses are understood: `#` headings, `*` list items,
+//! code spans, `<url>` and `[text](url)` links.
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use crate::{
+    game_state::GameState,
+    input::PrimaryButton,
+    loading::{self, Fonts},
+};
+
+pub struct Plugin;
+
+#[derive(Clone, Debug, TypeUuid)]
+#[uuid = "3c9d7f25-6b1e-4a08-8d4f-5e2a91c0b7d4"]
+pub struct Credits {
+    pub lines: Vec<Line>,
+}
+
+#[derive(Clone, Debug)]
+pub enum Line {
+    Heading(String),
+    Text(String),
+    Blank,
+}
+
+struct CreditsEntity;
+
+struct Scroll {
+    /// How far the credits have scrolled up.
+    offset: f32,
+    /// From the first line to the bottom of the last.
+    height: f32,
+}
+
+#[derive(Default)]
+struct CreditsLoader;
+
+/// Pixels per second.
+const SCROLL_SPEED: f32 = 40.;
+/// Where the first line starts, just below the bottom of the screen.
+const START_Y: f32 = -320.;
+const TOP_Y: f32 = 320.;
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_asset::<Credits>()
+            .init_asset_loader::<CreditsLoader>()
+            .insert_resource(Scroll {
+                offset: 0.,
+                height: 0.,
+            })
+            .add_system_set(
+                SystemSet::on_enter(GameState::Credits)
+                    .with_system(on_enter.system()))
+            .add_system_set(
+                SystemSet::on_update(GameState::Credits)
+                    .with_system(scroll.system()))
+            .add_system_set(
+                SystemSet::on_exit(GameState::Credits)
+                    .with_system(cleanup.system()))
+            ;
+    }
+}
+
+impl AssetLoader for CreditsLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let markdown = std::str::from_utf8(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(Credits::parse(markdown)));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["md"]
+    }
+}
+
+impl Credits {
+    fn parse(markdown: &str) -> Credits {
+        let lines = markdown.lines()
+            .map(|line| {
+                let line = line.trim();
+                if line.is_empty() {
+                    Line::Blank
+                } else if let Some(heading) = line.strip_prefix('#') {
+                    Line::Heading(plain_text(heading.trim_start_matches('#').trim()))
+                } else {
+                    Line::Text(plain_text(line.strip_prefix("* ").unwrap_or(line)))
+                }
+            })
+            .collect();
+        Credits { lines }
+    }
+}
+
+impl Line {
+    fn height(&self) -> f32 {
+        match self {
+            Line::Heading(_) => 60.,
+            Line::Text(_) => 24.,
+            Line::Blank => 16.,
+        }
+    }
+}
+
+/// Strips the markdown from a line, leaving the text of links and their URL.
+fn plain_text(markdown: &str) -> String {
+    let mut text = String::with_capacity(markdown.len());
+    let mut rest = markdown;
+    while let Some(c) = rest.chars().next() {
+        rest = &rest[c.len_utf8()..];
+        match c {
+            '`' | '<' | '>' => {},
+            // `[text](url)` becomes `text (url)`.
+            ']' if rest.starts_with('(') => text.push(' '),
+            '[' => {},
+            _ => text.push(c),
+        }
+    }
+    text
+}
+
+fn on_enter(
+    mut commands: Commands,
+    credits_assets: Res<Assets<Credits>>,
+    data: Res<loading::Data>,
+    fonts: Res<Fonts>,
+    mut scroll: ResMut<Scroll>,
+) {
+    let credits = credits_assets.get(&data.credits)
+        .expect("Credits should be loaded");
+
+    let alignment = TextAlignment {
+        vertical: VerticalAlign::Top,
+        horizontal: HorizontalAlign::Center,
+    };
+
+    let mut y = START_Y;
+    for line in credits.lines.iter() {
+        let style = match line {
+            Line::Heading(text) => Some((text, TextStyle {
+                font: fonts.fiendish.clone(),
+                font_size: 50.,
+                color: Color::rgb(242./255., 0., 48./255.),
+            })),
+            Line::Text(text) => Some((text, TextStyle {
+                font: fonts.fira_sans.clone(),
+                font_size: 16.,
+                color: Color::WHITE,
+            })),
+            Line::Blank => None,
+        };
+        if let Some((text, style)) = style {
+            commands.spawn_bundle(Text2dBundle {
+                text: Text::with_section(text.clone(), style, alignment),
+                transform: Transform {
+                    translation: Vec3::new(0., y, 10.),
+                    .. Default::default()
+                },
+                .. Default::default()
+            }).insert(CreditsEntity);
+        }
+        y -= line.height();
+    }
+
+    *scroll = Scroll {
+        offset: 0.,
+        height: START_Y - y,
+    };
+}
+
+/// Moves the credits up, and returns to the menu once they've gone off the top or the
+/// button is pressed.
+fn scroll(
+    mut button: PrimaryButton,
+    mut query: Query<&mut Transform, With<CreditsEntity>>,
+    mut scroll: ResMut<Scroll>,
+    mut state: ResMut<State<GameState>>,
+    time: Res<Time>,
+) {
+    let delta = SCROLL_SPEED * time.delta_seconds();
+    scroll.offset += delta;
+    for mut transform in query.iter_mut() {
+        transform.translation.y += delta;
+    }
+
+    if button.take() || scroll.offset > scroll.height + TOP_Y - START_Y {
+        state.set(GameState::Menu).unwrap();
+    }
+}
+
+fn cleanup(
+    mut commands: Commands,
+    query: Query<Entity, With<CreditsEntity>>,
+) {
+    for ent in query.iter() {
+        commands.entity(ent).despawn();
+    }
+}
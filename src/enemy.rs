@@ -1,14 +1,17 @@
 use bevy::prelude::*;
 use bevy_kira_audio::Audio;
 use crate::{
-    components::{AnimateSpriteSheet, AttackType, Character, DespawnAfter, Enemy, Health},
-    events::{Damage, DamageApplied, EnemyAttackTime, PlayerAttackAction},
+    campaign::Campaign,
+    components::{AnimateSpriteSheet, AttackType, Character, DespawnAfter, Enemy, Flashing,
+                 Health},
+    events::{Damage, DamageApplied, EnemyAttackTime, PlayerAttackAction, RunEnded},
     game_state::GameState,
+    health_bar::{HealthBar, HealthBarStyle},
+    layout::{Anchor, Anchored},
     loading::{self, Fonts, Sounds},
-    resources::Level,
+    resources::{Checkpoint, Coins, Level, Resumed, RunRng, RunStart, Upgrades},
     types::DamageType,
 };
-use rand::Rng;
 use std::time::Duration;
 
 pub struct Plugin;
@@ -27,31 +30,29 @@ struct Sprites {
     boss: CharacterSprites,
     magic_ball: Handle<ColorMaterial>,
     ray: Handle<TextureAtlas>,
-    health_background: Handle<ColorMaterial>,
-    health_bar: Handle<ColorMaterial>,
+    health_bar: HealthBarStyle,
     boss_text: Handle<TextureAtlas>,
     win_text: Handle<ColorMaterial>,
     level_border: Handle<ColorMaterial>,
     mouthers: Handle<TextureAtlas>,
 }
 
-struct AttackAnimation {
-    until: std::time::Duration,
+pub struct AttackAnimation {
+    pub until: std::time::Duration,
 }
 
-struct HpBackground;
-struct HpBar;
 struct LevelText;
 
 struct EnemyEntity;
 
 #[derive(Debug)]
-struct RespawnTimer {
-    at: std::time::Duration,
+pub struct RespawnTimer {
+    pub at: std::time::Duration,
 }
 
 pub const ATTACK_DURATION: Duration = Duration::from_millis(300);
-pub const NUM_MOB_LEVELS: u8 = 5;
+/// Wait between enemies within a stage.
+const FIGHT_REST: Duration = Duration::from_secs(2);
 
 impl bevy::app::Plugin for Plugin {
     fn build(&self, app: &mut AppBuilder) {
@@ -187,8 +188,10 @@ fn create_resources(
                                         )),
 
         magic_ball: materials.add(texture_assets.icon_magic.clone().into()),
-        health_background: materials.add(texture_assets.health_enemy.clone().into()),
-        health_bar: materials.add(Color::rgb(1.0, 0., 242./255.).into()),
+        health_bar: HealthBarStyle {
+            background: materials.add(texture_assets.health_enemy.clone().into()),
+            fill: materials.add(Color::rgb(1.0, 0., 242./255.).into()),
+        },
         win_text: materials.add(texture_assets.win_text.clone().into()),
         level_border: materials.add(texture_assets.level_border.clone().into()),
     });
@@ -200,37 +203,61 @@ fn insert_level(
     mut commands: Commands,
 ) {
     commands.insert_resource(
-        Level::Mob(1)
+        Level::default()
     );
+    commands.insert_resource(RunStart::New);
+    commands.insert_resource(RunRng::default());
+    commands.insert_resource(Resumed::default());
+    commands.insert_resource(Checkpoint {
+        stage: 0,
+        continues_left: 0,
+    });
 }
 
 fn set_level(
+    campaign: Res<Campaign>,
+    mut checkpoint: ResMut<Checkpoint>,
     mut level: ResMut<Level>,
+    mut run_rng: ResMut<RunRng>,
+    run_start: Res<RunStart>,
 ) {
-    *level = Level::Mob(1);
+    *level = match *run_start {
+        RunStart::New => {
+            run_rng.start_run();
+            *checkpoint = Checkpoint {
+                stage: 0,
+                continues_left: campaign.continues,
+            };
+            Level::default()
+        },
+        RunStart::Continue => Level {
+            stage: checkpoint.stage,
+            fight: 0,
+        },
+        RunStart::Resume => level.clone(),
+    };
 }
 
 fn spawn_current_enemy(
     mut commands: Commands,
     despawn_query: Query<Entity, With<EnemyEntity>>,
+    campaign: Res<Campaign>,
     fonts: Res<Fonts>,
     level: ResMut<Level>,
+    mut resumed: ResMut<Resumed>,
+    mut run_rng: ResMut<RunRng>,
     sprites: Res<Sprites>,
     time: Res<Time>,
 ) {
     // Despawn any entities from previous runs
     for entity in despawn_query.iter() {
-        commands.entity(entity).despawn();
+        commands.entity(entity).despawn_recursive();
     }
 
-    let character = match *level {
-        Level::Mob(_mob_level) => match rand::thread_rng().gen_range(0..=2) {
-            0 => Character::Archer,
-            1 => Character::Knight,
-            2 => Character::Mage,
-            _ => unreachable!(),
-        },
-        Level::Boss => Character::Boss,
+    let stage = &campaign.stages[level.stage];
+    let (character, resumed_hp) = match resumed.enemy.take() {
+        Some((character, hp)) => (character, Some(hp)),
+        None => (stage.enemies.character(level.fight, &mut run_rng.rng()), None),
     };
     let character_sprites: CharacterSprites = match character {
         Character::Archer => sprites.archer.clone(),
@@ -249,18 +276,22 @@ fn spawn_current_enemy(
     };
 
     let health = Health {
-        current: start_hp,
+        current: resumed_hp.unwrap_or(start_hp).min(start_hp),
         max: start_hp,
         vulnerable_to:
         match character {
             Character::Archer => vec![DamageType::Arrow, DamageType::Magic],
-            Character::Knight => vec![DamageType::Magic],
-            Character::Mage   => vec![DamageType::Arrow, DamageType::Sword],
+            Character::Knight => vec![DamageType::Magic, DamageType::Pierce],
+            Character::Mage   => vec![DamageType::Arrow, DamageType::Sword, DamageType::Light],
             Character::Boss   => vec![DamageType::Magic, DamageType::Sword],
             Character::Player => unreachable!(),
         }
         ,
     };
+    let y = match character {
+        Character::Boss => 150.,
+        _ => 173.
+    };
     commands.spawn()
         .insert(Enemy)
         .insert(EnemyEntity)
@@ -283,12 +314,7 @@ fn spawn_current_enemy(
             },
             texture_atlas: character_sprites.idle.clone(),
             transform: Transform {
-                translation: Vec3::new(163.,
-                                       match character {
-                                           Character::Boss => 150.,
-                                           _ => 173.
-                                       },
-                                       3.),
+                translation: Vec3::new(163., y, 3.),
                 scale: Vec3::ONE * (match character {
                     Character::Boss => 3.,
                     _ => 2.,
@@ -297,36 +323,18 @@ fn spawn_current_enemy(
             },
             .. Default::default()
         })
-        .insert(AnimateSpriteSheet::never());
-
-    // Spawn HP bar
-    commands.spawn_bundle(SpriteBundle {
-        material: sprites.health_background.clone(),
-        transform: Transform {
-            translation: Vec3::new(163., 243., 3.),
-            .. Default::default()
-        },
-        .. Default::default()
-    }).insert(HpBackground)
-      .insert(EnemyEntity);
-
-
-    commands.spawn_bundle(SpriteBundle {
-        material: sprites.health_bar.clone(),
-        sprite: Sprite::new(Vec2::new(1.0, 1.0)),
-        transform: health_bar_transform(&health),
-        .. Default::default()
-    }).insert(HpBar)
-      .insert(EnemyEntity);
+        .insert(AnimateSpriteSheet::never())
+        .with_children(|parent| {
+            // Every enemy's bar sits at the same height, whatever its size.
+            parent.spawn()
+                .insert(HealthBar::new(sprites.health_bar.clone(), Vec2::new(0., 243. - y)));
+        });
 
 
     // Spawn level text
     commands.spawn_bundle(Text2dBundle {
         text: Text::with_section(
-            match *level {
-                Level::Mob(n) => format!("Level {} of {}", n, NUM_MOB_LEVELS + 1),
-                Level::Boss => "Boss Level!".to_owned(),
-            },
+            stage.name.clone(),
             TextStyle {
                 font: fonts.fiendish.clone(),
                 font_size: 30.,
@@ -342,7 +350,31 @@ fn spawn_current_enemy(
         },
         .. Default::default()
     }).insert(LevelText)
+      .insert(Anchored::new(Anchor::TopLeft))
       .insert(EnemyEntity);
+
+    if let (0, Some(intro)) = (level.fight, &stage.intro) {
+        commands.spawn_bundle(Text2dBundle {
+            text: Text::with_section(
+                intro.clone(),
+                TextStyle {
+                    font: fonts.fiendish.clone(),
+                    font_size: 50.,
+                    color: Color::rgb(242./255., 0., 48./255.),
+                },
+                TextAlignment {
+                    vertical: VerticalAlign::Center,
+                    horizontal: HorizontalAlign::Center,
+                }),
+            transform: Transform {
+                translation: Vec3::new(0., 0., 6.),
+                .. Default::default()
+            },
+            .. Default::default()
+        }).insert(DespawnAfter {
+            after: time.time_since_startup() + Duration::from_secs(3),
+        });
+    }
     commands.spawn_bundle(SpriteBundle {
         material: sprites.level_border.clone(),
         transform: Transform {
@@ -351,7 +383,8 @@ fn spawn_current_enemy(
             .. Default::default()
         },
         .. Default::default()
-    }).insert(EnemyEntity);
+    }).insert(Anchored::new(Anchor::TopLeft))
+      .insert(EnemyEntity);
 
     // Mouthers
     commands.spawn()
@@ -441,7 +474,8 @@ fn enemy_attack(
                                 max_index: atlases.get(sprites.ray.clone())
                                     .map(|a| a.len() - 1).unwrap_or(0) as u32,
                                 loop_: false,
-                            });
+                            })
+                            .insert(Flashing);
                     },
                     _ => {},
                 };
@@ -471,12 +505,13 @@ fn end_attack_animation(
 
 fn update_enemy_hp(
     mut commands: Commands,
-    mut hp_bar: Query<&mut Transform, With<HpBar>>,
     mut enemy: Query<(Entity, &Health, &mut Handle<TextureAtlas>, &mut TextureAtlasSprite,
                       &mut AnimateSpriteSheet, &CharacterSprites),
                      With<Enemy>>,
     respawn_timer_query: Query<&RespawnTimer, With<Enemy>>,
     audio: Res<Audio>,
+    campaign: Res<Campaign>,
+    mut coins: ResMut<Coins>,
     level: Res<Level>,
     sounds: Res<Sounds>,
     sprites: Res<Sprites>,
@@ -498,9 +533,13 @@ fn update_enemy_hp(
                         .map(|a| a.len() - 1).unwrap_or(0) as u32,
                     loop_: false,
                 };
-                let boss_next = *level == Level::Mob(NUM_MOB_LEVELS);
-                let boss_done = *level == Level::Boss;
-                if boss_done {
+                coins.0 += health.max;
+                let stage = &campaign.stages[level.stage];
+                let stage_done = level.fight + 1 >= stage.enemies.num_fights();
+                let campaign_done = stage_done && level.stage + 1 >= campaign.stages.len();
+                let boss_next = stage_done && !campaign_done
+                    && campaign.stages[level.stage + 1].boss_intro;
+                if campaign_done {
                     audio.play(sounds.zombie_death.clone());
                     commands.spawn_bundle(SpriteBundle {
                         material: sprites.win_text.clone(),
@@ -534,7 +573,7 @@ fn update_enemy_hp(
                         next_frame_time: time.time_since_startup() + Duration::from_millis(500),
                         max_index: 1,
                         loop_: true,
-                    });
+                    }).insert(Flashing);
                 } else {
                     // Just a regular level
                     audio.play(sounds.zombie_death.clone());
@@ -543,19 +582,14 @@ fn update_enemy_hp(
                 commands.entity(enemy_entity)
                     .insert(RespawnTimer {
                         at: time.time_since_startup() +
-                            if boss_next {
-                                Duration::from_secs(5)
-                            } else if boss_done {
-                                Duration::from_secs(10)
+                            if stage_done {
+                                Duration::from_secs_f32(stage.rest_secs)
                             } else {
-                                Duration::from_secs(2)
+                                FIGHT_REST
                             }
                     });
             }
         }
-         for mut hp_transform in hp_bar.single_mut() {
-             *hp_transform = health_bar_transform(health);
-         }
     }
 }
 
@@ -577,27 +611,43 @@ fn respawn_timer(
     commands: Commands,
     respawn_query: Query<&RespawnTimer, With<Enemy>>,
     despawn_query: Query<Entity, With<EnemyEntity>>,
+    campaign: Res<Campaign>,
+    mut checkpoint: ResMut<Checkpoint>,
     fonts: Res<Fonts>,
     mut level: ResMut<Level>,
+    resumed: ResMut<Resumed>,
+    mut run_ended_writer: EventWriter<RunEnded>,
+    run_rng: ResMut<RunRng>,
     sprites: Res<Sprites>,
     mut state: ResMut<State<GameState>>,
     time: Res<Time>,
 ) {
     if let Ok(timer) = respawn_query.single() {
         if time.time_since_startup() > timer.at {
-            if *level == Level::Boss {
+            let num_fights = campaign.stages[level.stage].enemies.num_fights();
+            if level.fight + 1 < num_fights {
+                level.fight += 1;
+                spawn_current_enemy(commands, despawn_query, campaign, fonts, level, resumed,
+                                    run_rng, sprites, time);
+                return;
+            }
+
+            if level.stage + 1 >= campaign.stages.len() {
+                run_ended_writer.send(RunEnded { won: true });
                 state.set(GameState::Menu).unwrap();
                 return;
             }
-            *level = match *level {
-                Level::Mob(n) if n == NUM_MOB_LEVELS => Level::Boss,
-                Level::Mob(n) => {
-                    assert!(n < u8::MAX);
-                    Level::Mob(n + 1)
-                },
-                Level::Boss => unreachable!(),
+            *level = Level {
+                stage: level.stage + 1,
+                fight: 0,
             };
-            spawn_current_enemy(commands, despawn_query, fonts, level, sprites, time);
+            if campaign.stages[level.stage].checkpoint {
+                checkpoint.stage = level.stage;
+            }
+            spawn_current_enemy(commands, despawn_query, campaign, fonts, level, resumed, run_rng,
+                                sprites, time);
+            // Visit the shop before fighting the enemy just spawned.
+            state.push(GameState::Shop).unwrap();
         }
     }
 }
@@ -606,27 +656,16 @@ fn enemy_was_attacked(
     mut player_attack_reader: EventReader<PlayerAttackAction>,
     mut damage_writer: EventWriter<Damage>,
     enemy_query: Query<Entity, With<Enemy>>,
+    upgrades: Res<Upgrades>,
 ) {
     if let Some(attack) = player_attack_reader.iter().next() {
         for enemy in enemy_query.single() {
             damage_writer.send(Damage {
                 target: enemy,
-                hp: 1,
+                hp: attack.hp + upgrades.damage_bonus(&attack.damage_type),
                 damage_type: attack.damage_type.clone(),
+                crit: attack.crit,
             });
         }
     }
 }
-
-fn health_bar_transform(health: &Health) -> Transform {
-    assert!(health.max >= 1);
-    let portion = (health.current as f32) / (health.max as f32);
-    const WIDTH: f32 = 162.;
-    let width_pixels = portion * WIDTH;
-    let left_edge = 163. - (WIDTH / 2.);
-    Transform {
-        translation: Vec3::new(left_edge + width_pixels / 2., 243., 4.),
-        scale: Vec3::new(width_pixels, 16., 1.),
-        .. Default::default()
-    }
-}
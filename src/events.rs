@@ -1,16 +1,65 @@
 //! Shared events
 
 use bevy::prelude::*;
-use crate::types::{DamageType, Hp};
+use crate::{
+    components::Action,
+    types::{DamageType, Hp, Judgement},
+};
+
+/// Hits closer to the icon than this are perfect.
+const PERFECT_SECS: f64 = 0.05;
 
 #[derive(Debug)]
 pub struct PlayerAttackAction {
     pub damage_type: DamageType,
+    pub hp: Hp,
+    /// Pressed perfectly on time, shown as a critical hit.
+    pub crit: bool,
+}
+/// The one button was pressed during a fight.
+pub struct ButtonPressed {
+    /// `MusicTime::song_beat` when pressed. The action is timed against this rather than the
+    /// pointer's current position, so replayed presses are timed as they were recorded.
+    pub song_beat: f64,
 }
+
 pub struct PlayerDefendAction;
+pub struct PlayerHealAction;
+
+/// `target` gained health or max health, from the Heal action or the shop.
+pub struct Healed {
+    pub target: Entity,
+}
+
+/// The button was pressed during a fight, timed against the action nearest the pointer.
+#[derive(Debug)]
+pub struct ActionTimed {
+    pub action: Action,
+    /// Whether the press was inside the action's hit window.
+    pub hit: bool,
+    /// Negative if early, positive if late.
+    pub offset_secs: f64,
+}
+
+impl ActionTimed {
+    pub fn judgement(&self) -> Judgement {
+        if !self.hit {
+            Judgement::Miss
+        } else if self.offset_secs.abs() <= PERFECT_SECS {
+            Judgement::Perfect
+        } else {
+            Judgement::Good
+        }
+    }
+}
 
 pub struct EnemyAttackTime;
 
+/// An enemy attack landed, or the player defended it.
+pub struct EnemyAttackOutcome {
+    pub defended: bool,
+}
+
 /// Event representing an attempt to damage an entity with Health component.
 /// May be blocked if the DamageType is incorrect.
 #[derive(Clone)]
@@ -18,6 +67,8 @@ pub struct Damage {
     pub target: Entity,
     pub hp: Hp,
     pub damage_type: DamageType,
+    /// A critical hit, which only changes the floating text.
+    pub crit: bool,
 }
 
 /// Event representing some damage that went through.
@@ -25,12 +76,28 @@ pub struct DamageApplied {
     pub damage: Damage,
 }
 
+/// Event representing some damage that was blocked because the target wasn't vulnerable.
+pub struct DamageBlocked {
+    pub damage: Damage,
+}
+
 pub struct Die {
     pub target: Entity,
 }
 
+/// The player died or beat the campaign.
+pub struct RunEnded {
+    pub won: bool,
+}
+
 #[derive(Debug)]
 pub struct MusicTime {
     pub loop_position: f64,
+    /// Beats since the start of the track.
+    pub beat: f64,
     pub beat_in_bar: f64,
+    /// Beats since the music started, which keeps counting when the track loops. The music
+    /// is paused with the game and in the shop, so this is the time through the run.
+    pub song_beat: f64,
+    pub beat_secs: f64,
 }
@@ -4,18 +4,20 @@ use crate::{
     components::{AttackType, Enemy, Health, Player},
     enemy,
     events::Damage,
-    events::{EnemyAttackTime, PlayerDefendAction},
+    events::{EnemyAttackOutcome, EnemyAttackTime, PlayerDefendAction},
     Icons,
     game_state::GameState,
     loading::Sounds,
+    replay::ReplayState,
+    settings::Settings,
 };
 use std::time::Duration;
 
 pub struct Plugin;
 
 struct FightIcon;
-struct HideAfter {
-    when: Duration,
+pub struct HideAfter {
+    pub when: Duration,
 }
 
 /// Is the player defending this beat?
@@ -46,11 +48,14 @@ fn show_fight_icons(
     mut commands: Commands,
     mut enemy_attack_time_reader: EventReader<EnemyAttackTime>,
     mut damage_writer: EventWriter<Damage>,
+    mut outcome_writer: EventWriter<EnemyAttackOutcome>,
     player_query: Query<Entity, With<Player>>,
     enemy_query: Query<(&Health, &AttackType), With<Enemy>>,
     audio: Res<Audio>,
     icons: Res<Icons>,
     mut player_defend: ResMut<PlayerDefend>,
+    replay_state: Res<ReplayState>,
+    settings: Res<Settings>,
     sounds: Res<Sounds>,
     time: Res<Time>,
 ) {
@@ -59,8 +64,9 @@ fn show_fight_icons(
             return;
         }
         if let Some(_) = enemy_attack_time_reader.iter().next() {
-            let did_defend = player_defend.0;
+            let did_defend = player_defend.0 || replay_state.accessibility(&settings).auto_defend;
             player_defend.0 = false;
+            outcome_writer.send(EnemyAttackOutcome { defended: did_defend });
             if did_defend {
                 commands.spawn_bundle(SpriteBundle {
                     material: icons.defend.clone(),
@@ -81,6 +87,7 @@ fn show_fight_icons(
                         target: player_entity,
                         hp: 1,
                         damage_type: enemy_attack_type.damage_type.clone(),
+                        crit: false,
                     });
                 }
             }
@@ -0,0 +1,120 @@
+//! Combat text that rises from where it's spawned and fades out: damage numbers, blocks,
+//! crits and the judgement of each press.
+
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::*,
+};
+use crate::{
+    components::DespawnAfter,
+    events::ActionTimed,
+    game_state::GameState,
+    loading::Fonts,
+    types::DamageType,
+};
+use std::time::Duration;
+
+pub struct Plugin;
+
+/// Spawns floating text from any system.
+#[derive(SystemParam)]
+pub struct FloatingTexts<'a> {
+    commands: Commands<'a>,
+    fonts: Res<'a, Fonts>,
+    time: Res<'a, Time>,
+}
+
+pub struct FloatingText {
+    start: Vec3,
+    /// Moved on by the time spent paused.
+    pub spawned: Duration,
+    color: Color,
+}
+
+const FLOAT_SECS: f32 = 0.8;
+/// How far the text rises before it's gone.
+const RISE: f32 = 60.;
+/// Judgements float up over the middle of the action spinner.
+const JUDGEMENT_X: f32 = -200.;
+const JUDGEMENT_Y: f32 = 30.;
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(judgements.system())
+                    .with_system(animate.system()))
+            ;
+    }
+}
+
+impl<'a> FloatingTexts<'a> {
+    /// `position` is where the text starts, it rises from there.
+    pub fn spawn(&mut self, value: &str, color: Color, font_size: f32, position: Vec3) {
+        let now = self.time.time_since_startup();
+        self.commands.spawn_bundle(Text2dBundle {
+            text: Text::with_section(
+                value,
+                TextStyle {
+                    font: self.fonts.fiendish.clone(),
+                    font_size,
+                    color,
+                },
+                TextAlignment {
+                    vertical: VerticalAlign::Center,
+                    horizontal: HorizontalAlign::Center,
+                }),
+            transform: Transform {
+                translation: position,
+                .. Default::default()
+            },
+            .. Default::default()
+        }).insert(FloatingText {
+            start: position,
+            spawned: now,
+            color,
+        }).insert(DespawnAfter {
+            after: now + Duration::from_secs_f32(FLOAT_SECS),
+        });
+    }
+}
+
+pub fn damage_color(damage_type: &DamageType) -> Color {
+    match damage_type {
+        DamageType::Arrow => Color::rgb(0.5, 0.9, 0.4),
+        DamageType::Magic => Color::rgb(0.6, 0.4, 1.),
+        DamageType::Sword => Color::SILVER,
+        DamageType::Ray => Color::CYAN,
+        // The same colours as their spinner icons.
+        DamageType::Pierce => Color::GOLD,
+        DamageType::Light => Color::ORANGE_RED,
+    }
+}
+
+fn judgements(
+    mut timed_reader: EventReader<ActionTimed>,
+    mut floating_texts: FloatingTexts,
+) {
+    for timed in timed_reader.iter() {
+        let judgement = timed.judgement();
+        floating_texts.spawn(judgement.name(), judgement.color(), 30.,
+                             Vec3::new(JUDGEMENT_X, JUDGEMENT_Y, 10.));
+    }
+}
+
+/// Rises quickly then slows down, while fading out faster towards the end.
+fn animate(
+    mut query: Query<(&FloatingText, &mut Transform, &mut Text)>,
+    time: Res<Time>,
+) {
+    let now = time.time_since_startup();
+    for (floating, mut transform, mut text) in query.iter_mut() {
+        let t = ((now - floating.spawned).as_secs_f32() / FLOAT_SECS).min(1.);
+        let rise = 1. - (1. - t).powi(3);
+        transform.translation = floating.start + Vec3::new(0., RISE * rise, 0.);
+        let mut color = floating.color;
+        color.set_a(floating.color.a() * (1. - t * t));
+        text.sections[0].style.color = color;
+    }
+}
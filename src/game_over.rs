@@ -2,11 +2,28 @@ use bevy::prelude::*;
 use bevy_kira_audio::Audio;
 use crate::{
     game_state::GameState,
-    loading::{self, Sounds},
+    input::PrimaryButton,
+    loading::{self, Fonts, Sounds},
+    music::Channels,
+    resources::{Checkpoint, RunStart},
 };
+use std::time::Duration;
 
 struct GameOver;
 
+/// One line of the options shown when the run can continue from a checkpoint.
+struct OptionText {
+    run_start: RunStart,
+}
+
+/// The highlighted option cycles by itself so the player only needs the one button.
+struct Selection {
+    run_start: RunStart,
+    next_cycle: Duration,
+}
+
+const CYCLE_PERIOD: Duration = Duration::from_millis(900);
+
 struct Sprites {
     press_space: Handle<ColorMaterial>,
     text: Handle<ColorMaterial>,
@@ -25,7 +42,9 @@ impl bevy::app::Plugin for Plugin {
                     .with_system(on_enter.system()))
             .add_system_set(
                 SystemSet::on_update(GameState::GameOver)
-                    .with_system(keyboard_input.system()))
+                    .with_system(cycle_selection.system())
+                    .with_system(keyboard_input.system())
+                    .with_system(update_text.system()))
             .add_system_set(
                 SystemSet::on_exit(GameState::GameOver)
                     .with_system(cleanup.system()))
@@ -42,13 +61,22 @@ fn create_resources(
         press_space: materials.add(texture_assets.game_over_press_space.clone().into()),
         text: materials.add(texture_assets.game_over_text.clone().into()),
     });
+    commands.insert_resource(Selection {
+        run_start: RunStart::Continue,
+        next_cycle: Duration::default(),
+    });
 }
 
 fn on_enter(
     mut commands: Commands,
     audio: Res<Audio>,
+    channels: Res<Channels>,
+    checkpoint: Res<Checkpoint>,
+    fonts: Res<Fonts>,
+    mut selection: ResMut<Selection>,
     sounds: Res<Sounds>,
     sprites: Res<Sprites>,
+    time: Res<Time>,
 ) {
     commands
         .spawn()
@@ -63,39 +91,118 @@ fn on_enter(
             .. Default::default()
         });
 
-    commands
-        .spawn()
-        .insert(GameOver)
-        .insert_bundle(SpriteBundle {
-            material: sprites.press_space.clone(),
-            transform: Transform {
-                translation: Vec3::new(0., -150., 10.),
-                scale: Vec3::ONE * 0.7,
+    if checkpoint.can_continue() {
+        *selection = Selection {
+            run_start: RunStart::Continue,
+            next_cycle: time.time_since_startup() + CYCLE_PERIOD,
+        };
+
+        let options = [
+            (RunStart::Continue,
+             format!("Continue from level {} ({} left)",
+                     checkpoint.stage + 1, checkpoint.continues_left)),
+            (RunStart::New, "Restart".to_owned()),
+        ];
+        for (i, (run_start, value)) in options.iter().enumerate() {
+            commands
+                .spawn()
+                .insert(GameOver)
+                .insert(OptionText {
+                    run_start: *run_start,
+                })
+                .insert_bundle(Text2dBundle {
+                    text: Text::with_section(
+                        value.clone(),
+                        TextStyle {
+                            font: fonts.fira_sans.clone(),
+                            font_size: 30.,
+                            color: Color::WHITE,
+                        },
+                        TextAlignment {
+                            vertical: VerticalAlign::Center,
+                            horizontal: HorizontalAlign::Center,
+                        }),
+                    transform: Transform {
+                        translation: Vec3::new(0., -130. - 50. * (i as f32), 10.),
+                        .. Default::default()
+                    },
+                    .. Default::default()
+                });
+        }
+    } else {
+        *selection = Selection {
+            run_start: RunStart::New,
+            next_cycle: Duration::MAX,
+        };
+
+        commands
+            .spawn()
+            .insert(GameOver)
+            .insert_bundle(SpriteBundle {
+                material: sprites.press_space.clone(),
+                transform: Transform {
+                    translation: Vec3::new(0., -150., 10.),
+                    scale: Vec3::ONE * 0.7,
+                    .. Default::default()
+                },
                 .. Default::default()
-            },
-            .. Default::default()
-        });
+            });
+    }
 
     audio.play(sounds.game_over.clone());
-    audio.play_looped(sounds.game_over_loop.clone());
+    audio.play_looped_in_channel(sounds.game_over_loop.clone(), &channels.music);
+}
+
+fn cycle_selection(
+    mut selection: ResMut<Selection>,
+    time: Res<Time>,
+) {
+    let now = time.time_since_startup();
+    if now >= selection.next_cycle {
+        selection.run_start = match selection.run_start {
+            RunStart::Continue => RunStart::New,
+            RunStart::New | RunStart::Resume => RunStart::Continue,
+        };
+        selection.next_cycle = now + CYCLE_PERIOD;
+    }
 }
 
 fn keyboard_input(
-    mut kb: ResMut<Input<KeyCode>>,
+    mut checkpoint: ResMut<Checkpoint>,
+    mut button: PrimaryButton,
+    mut run_start: ResMut<RunStart>,
+    selection: Res<Selection>,
     mut state: ResMut<State<GameState>>,
 ) {
-    if kb.just_pressed(KeyCode::Space) {
-        log::debug!("kb.just_pressed(Space)");
-        // .reset() space so Playing state doesn't think it's already pressed
-        kb.reset(KeyCode::Space);
+    if button.take() {
+        log::debug!("Primary button pressed");
+        *run_start = selection.run_start;
+        if *run_start == RunStart::Continue {
+            checkpoint.continues_left -= 1;
+        }
         state.set(GameState::Playing).unwrap();
     }
 }
 
+fn update_text(
+    mut query: Query<(&OptionText, &mut Text)>,
+    selection: Res<Selection>,
+) {
+    for (option, mut text) in query.iter_mut() {
+        text.sections[0].style.color =
+            if option.run_start == selection.run_start {
+                Color::rgb(242./255., 0., 48./255.)
+            } else {
+                Color::WHITE
+            };
+    }
+}
+
 fn cleanup(
     mut commands: Commands,
     query: Query<Entity, With<GameOver>>,
     audio: Res<Audio>,
+    channels: Res<Channels>,
 ) {
     for ent in query.iter() {
         commands
@@ -103,4 +210,5 @@ fn cleanup(
             .despawn();
     }
     audio.stop();
+    audio.stop_channel(&channels.music);
 }
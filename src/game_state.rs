@@ -2,6 +2,8 @@
 pub (crate) enum GameState {
     /// During the loading State the LoadingPlugin will load our assets
     Loading,
+    /// An asset failed to load, the failing paths are shown instead of carrying on
+    LoadingFailed,
     /// Asset loading is complete at this point but resources derived
     /// from the loaded assets need to be created
     CreateResources,
@@ -9,8 +11,25 @@ pub (crate) enum GameState {
     Setup,
     /// Here the menu is drawn and waiting for player interaction
     Menu,
+    /// The player picks which actions go on the action spinner
+    Loadout,
     /// During this state the actual game logic is executed
     Playing,
+    /// Between levels the player spends coins on upgrades.
+    /// Pushed on top of Playing so the run carries on when it's popped.
+    Shop,
+    /// The fight is paused. Pushed on top of Playing like Shop.
+    Paused,
     /// The player has died and there is the option to restart
     GameOver,
+    /// Lifetime stats across every run
+    Stats,
+    /// The enemies met so far and their weaknesses
+    Bestiary,
+    /// Choose a song to practice
+    SongSelect,
+    /// Audio, display and gameplay settings
+    Options,
+    /// Scrolling credits and asset attributions
+    Credits,
 }
@@ -0,0 +1,199 @@
+//! Shows how the best recorded run was doing at the same point in the music: its score,
+//! combo and HP, and where on the spinner it pressed the button during the current turn.
+
+use bevy::prelude::*;
+use crate::{
+    action_spinner,
+    game_state::GameState,
+    loading::{self, Fonts},
+    replay::{self, Clock, Replay, ReplayState, Snapshot},
+    resources::Score,
+    save::Saves,
+    settings::Settings,
+};
+pub struct Plugin;
+
+/// Centre of the action spinner.
+const SPINNER_X: f32 = -200.;
+const SPINNER_Y: f32 = 0.;
+/// Markers sit just outside the action icons.
+const MARKER_RADIUS: f32 = 140.;
+
+struct Ghost {
+    best: Option<Replay>,
+    /// Index into `best.snapshots` of the latest snapshot reached.
+    snapshot: Option<usize>,
+    /// Turn of the spinner the markers were spawned for.
+    marker_turn: Option<i64>,
+}
+
+struct Sprites {
+    marker: Handle<ColorMaterial>,
+}
+
+struct GhostEntity;
+struct GhostText;
+struct GhostMarker;
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .insert_resource(Ghost {
+                best: None,
+                snapshot: None,
+                marker_turn: None,
+            })
+            .add_system_set(
+                SystemSet::on_enter(GameState::CreateResources)
+                    .with_system(create_resources.system()))
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(on_enter.system()))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(update_text.system().after("replay_clock"))
+                    .with_system(update_markers.system().after("replay_clock")))
+            .add_system_set(
+                SystemSet::on_exit(GameState::Playing)
+                    .with_system(cleanup.system()))
+            ;
+    }
+}
+
+fn create_resources(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    texture_assets: Res<loading::TextureAssets>,
+) {
+    commands.insert_resource(Sprites {
+        marker: materials.add(ColorMaterial::modulated_texture(
+            texture_assets.ghost_marker.clone(), Color::rgba(1., 1., 1., 0.6))),
+    });
+}
+
+/// The best run is loaded for each run, as the last one may have beaten it.
+fn on_enter(
+    mut commands: Commands,
+    fonts: Res<Fonts>,
+    mut ghost: ResMut<Ghost>,
+    saves: Res<Saves>,
+    settings: Res<Settings>,
+) {
+    *ghost = Ghost {
+        best:
+            if settings.gameplay.show_ghost {
+                saves.load(replay::BEST_SAVE_KEY)
+            } else {
+                None
+            },
+        snapshot: None,
+        marker_turn: None,
+    };
+
+    if ghost.best.is_none() {
+        return;
+    }
+
+    commands.spawn_bundle(Text2dBundle {
+        text: Text::with_section(
+            "",
+            TextStyle {
+                font: fonts.fira_sans.clone(),
+                font_size: 18.,
+                color: Color::rgba(0.8, 0.6, 1., 0.9),
+            },
+            TextAlignment {
+                vertical: VerticalAlign::Center,
+                horizontal: HorizontalAlign::Center,
+            }),
+        transform: Transform {
+            translation: Vec3::new(SPINNER_X, 190., 10.),
+            .. Default::default()
+        },
+        .. Default::default()
+    }).insert(GhostText)
+      .insert(GhostEntity);
+}
+
+fn update_text(
+    mut query: Query<&mut Text, With<GhostText>>,
+    clock: Res<Clock>,
+    mut ghost: ResMut<Ghost>,
+    score: Res<Score>,
+) {
+    let ghost = &mut *ghost;
+    let best = match &ghost.best {
+        Some(b) => b,
+        None => return,
+    };
+
+    let mut next = ghost.snapshot.map(|i| i + 1).unwrap_or(0);
+    while next < best.snapshots.len() && best.snapshots[next].song_beat <= clock.song_beat {
+        ghost.snapshot = Some(next);
+        next += 1;
+    }
+    let snapshot: Option<&Snapshot> = ghost.snapshot.map(|i| &best.snapshots[i]);
+
+    for mut text in query.iter_mut() {
+        text.sections[0].value = match snapshot {
+            Some(s) => {
+                let ahead = score.points as i64 - s.score as i64;
+                format!("Best run: {}  Combo {}  HP {}\nYou're {} {}",
+                        s.score, s.combo, s.hp, ahead.abs(),
+                        if ahead < 0 { "behind" } else { "ahead" })
+            },
+            None => "Best run".to_owned(),
+        };
+    }
+}
+
+/// Show where the best run pressed the button during this turn of the spinner.
+fn update_markers(
+    mut commands: Commands,
+    markers: Query<Entity, With<GhostMarker>>,
+    clock: Res<Clock>,
+    mut ghost: ResMut<Ghost>,
+    replay_state: Res<ReplayState>,
+    settings: Res<Settings>,
+    sprites: Res<Sprites>,
+) {
+    let beats_per_turn = replay_state.accessibility(&settings).beats_per_turn();
+    let turn = (clock.song_beat / beats_per_turn).floor() as i64;
+    if ghost.best.is_none() || ghost.marker_turn == Some(turn) {
+        return;
+    }
+    ghost.marker_turn = Some(turn);
+
+    for ent in markers.iter() {
+        commands.entity(ent).despawn();
+    }
+
+    let best = ghost.best.as_ref().unwrap();
+    for press in best.presses.iter()
+        .filter(|p| (*p / beats_per_turn).floor() as i64 == turn)
+    {
+        // Same angle the action pointer has at this point in the turn.
+        let angle = action_spinner::pointer_angle(*press, beats_per_turn) as f32;
+        commands.spawn_bundle(SpriteBundle {
+            material: sprites.marker.clone(),
+            transform: Transform {
+                translation: Vec3::new(SPINNER_X - angle.sin() * MARKER_RADIUS,
+                                       SPINNER_Y + angle.cos() * MARKER_RADIUS,
+                                       2.),
+                scale: Vec3::ONE * 0.1,
+                .. Default::default()
+            },
+            .. Default::default()
+        }).insert(GhostMarker)
+          .insert(GhostEntity);
+    }
+}
+
+fn cleanup(
+    mut commands: Commands,
+    query: Query<Entity, With<GhostEntity>>,
+) {
+    for ent in query.iter() {
+        commands.entity(ent).despawn();
+    }
+}
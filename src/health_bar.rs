@@ -0,0 +1,158 @@
+//! A health bar that follows the `Health` of its parent entity.
+//!
+//! Spawn an entity with a `HealthBar` as a child of anything with `Health`, and the sprites
+//! are added and kept up to date. Lost health stays visible for a moment as a pale chip,
+//! which then shrinks down to the new health.
+
+use bevy::prelude::*;
+use crate::{
+    components::Health,
+    game_state::GameState,
+};
+
+pub struct Plugin;
+
+/// How the bar looks, one for each kind of character.
+#[derive(Clone)]
+pub struct HealthBarStyle {
+    pub background: Handle<ColorMaterial>,
+    pub fill: Handle<ColorMaterial>,
+}
+
+pub struct HealthBar {
+    style: HealthBarStyle,
+    /// From the parent's position, in world units so the parent's scale doesn't matter.
+    offset: Vec2,
+    /// Portions of health shown by the bar and the chip, from the last update.
+    last_portion: Option<f32>,
+    chip: Option<f32>,
+    /// Seconds before the chip starts shrinking.
+    chip_hold_secs: f32,
+}
+
+#[derive(Clone, Copy)]
+enum Part {
+    Fill,
+    Chip,
+}
+
+struct Sprites {
+    chip: Handle<ColorMaterial>,
+}
+
+/// Size of the bar inside the border of the background sprites.
+const WIDTH: f32 = 162.;
+const HEIGHT: f32 = 16.;
+const CHIP_HOLD_SECS: f32 = 0.4;
+/// Portion of the full bar the chip shrinks by each second.
+const CHIP_SPEED: f32 = 0.8;
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_system_set(
+                SystemSet::on_enter(GameState::CreateResources)
+                    .with_system(create_resources.system()))
+            .add_system(add_sprites.system())
+            // Fights are the only place with health bars, and the chip holds still while
+            // one is paused or in the shop.
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(update_bars.system()));
+    }
+}
+
+impl HealthBar {
+    pub fn new(style: HealthBarStyle, offset: Vec2) -> HealthBar {
+        HealthBar {
+            style,
+            offset,
+            last_portion: None,
+            chip: None,
+            chip_hold_secs: 0.,
+        }
+    }
+}
+
+fn create_resources(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands.insert_resource(Sprites {
+        chip: materials.add(Color::rgb(1., 1., 0.8).into()),
+    });
+}
+
+fn add_sprites(
+    mut commands: Commands,
+    query: Query<(Entity, &HealthBar), Added<HealthBar>>,
+    sprites: Res<Sprites>,
+) {
+    for (entity, bar) in query.iter() {
+        commands.entity(entity)
+            .insert_bundle(SpriteBundle {
+                material: bar.style.background.clone(),
+                .. Default::default()
+            })
+            .with_children(|parent| {
+                for (part, material, z) in [(Part::Chip, sprites.chip.clone(), 0.1),
+                                            (Part::Fill, bar.style.fill.clone(), 0.2)].iter() {
+                    parent.spawn_bundle(SpriteBundle {
+                        material: material.clone(),
+                        sprite: Sprite::new(Vec2::new(1.0, 1.0)),
+                        transform: Transform::from_xyz(0., 0., *z),
+                        .. Default::default()
+                    }).insert(*part);
+                }
+            });
+    }
+}
+
+fn update_bars(
+    mut bar_query: Query<(&mut HealthBar, &Parent, &mut Transform, &Children)>,
+    owner_query: Query<(&Health, &Transform), (Without<HealthBar>, Without<Part>)>,
+    mut part_query: Query<(&Part, &mut Transform), Without<HealthBar>>,
+    time: Res<Time>,
+) {
+    for (mut bar, parent, mut transform, children) in bar_query.iter_mut() {
+        let (health, owner_transform) = match owner_query.get(parent.0) {
+            Ok(o) => o,
+            Err(_) => continue,
+        };
+
+        let portion = (health.current as f32 / health.max.max(1) as f32).clamp(0., 1.);
+        let mut chip = bar.chip.unwrap_or(portion);
+        if portion < bar.last_portion.unwrap_or(portion) {
+            // Just lost health, hold the chip where it is for a moment.
+            bar.chip_hold_secs = CHIP_HOLD_SECS;
+        }
+        if portion >= chip {
+            chip = portion;
+        } else if bar.chip_hold_secs > 0. {
+            bar.chip_hold_secs -= time.delta_seconds();
+        } else {
+            chip = (chip - CHIP_SPEED * time.delta_seconds()).max(portion);
+        }
+        bar.chip = Some(chip);
+        bar.last_portion = Some(portion);
+
+        // Undo the parent's scale, so every bar is the same size.
+        let owner_scale = owner_transform.scale.x;
+        *transform = Transform {
+            translation: bar.offset.extend(1.) / owner_scale,
+            scale: Vec3::ONE / owner_scale,
+            .. Default::default()
+        };
+
+        for child in children.iter() {
+            if let Ok((part, mut part_transform)) = part_query.get_mut(*child) {
+                let width = WIDTH * match part {
+                    Part::Fill => portion,
+                    Part::Chip => chip,
+                };
+                part_transform.translation.x = (width - WIDTH) / 2.;
+                part_transform.scale = Vec3::new(width, HEIGHT, 1.);
+            }
+        }
+    }
+}
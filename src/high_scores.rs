@@ -0,0 +1,284 @@
+use bevy::prelude::*;
+use crate::{
+    campaign::{Campaign, Song},
+    events::RunEnded,
+    game_state::GameState,
+    layout::{Anchor, Anchored},
+    loading::Fonts,
+    replay,
+    resources::{Difficulty, Level, Mode, RunRng, Score},
+    save::{self, Saves},
+    types::Hp,
+};
+use serde::{Deserialize, Serialize};
+
+/// Records the best runs for each song, difficulty and mode, and shows them on the menu.
+pub struct Plugin;
+
+const SAVE_KEY: &str = "high_scores";
+const ENTRIES_PER_TABLE: usize = 10;
+const ENTRIES_ON_MENU: usize = 3;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Entry {
+    /// The run's seed. A continued run ends once per game over, and each time replaces its
+    /// entry.
+    #[serde(default)]
+    pub run_id: u64,
+    pub score: u32,
+    pub unix_secs: u64,
+    pub won: bool,
+    /// 1 indexed campaign stage.
+    pub stage_reached: usize,
+    pub max_combo: u32,
+    pub hits: u32,
+    pub damage_taken: Hp,
+    pub continues_used: u8,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct Table {
+    song: Song,
+    difficulty: Difficulty,
+    mode: Mode,
+    /// Sorted best first.
+    entries: Vec<Entry>,
+}
+
+#[derive(Default, Deserialize, Serialize)]
+pub struct HighScores {
+    tables: Vec<Table>,
+}
+
+struct HighScoresText;
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_system_set(
+                SystemSet::on_enter(GameState::CreateResources)
+                    .with_system(create_resources.system()))
+            .add_system_set(
+                SystemSet::on_enter(GameState::Menu)
+                    .with_system(spawn_text.system()))
+            .add_system_set(
+                SystemSet::on_exit(GameState::Menu)
+                    .with_system(cleanup.system()))
+            // RunEnded is sent just before leaving Playing.
+            .add_system_set(
+                replay::live_on_exit()
+                    .with_system(record_run.system().before("replay_end")));
+    }
+}
+
+impl HighScores {
+    pub fn entries(&self, song: Song, difficulty: Difficulty, mode: Mode) -> &[Entry] {
+        self.tables.iter()
+            .find(|t| t.song == song && t.difficulty == difficulty && t.mode == mode)
+            .map(|t| t.entries.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Returns the 0 indexed rank of the new entry, or `None` if it didn't make the table.
+    ///
+    /// An entry from the same run is replaced. A run's score never goes down, so the new
+    /// entry always makes the table when the old one did.
+    fn insert(&mut self, song: Song, difficulty: Difficulty, mode: Mode, entry: Entry)
+              -> Option<usize>
+    {
+        let table_idx =
+            match self.tables.iter()
+                .position(|t| t.song == song && t.difficulty == difficulty && t.mode == mode)
+            {
+                Some(idx) => idx,
+                None => {
+                    self.tables.push(Table {
+                        song,
+                        difficulty,
+                        mode,
+                        entries: Vec::new(),
+                    });
+                    self.tables.len() - 1
+                }
+            };
+        let entries = &mut self.tables[table_idx].entries;
+        entries.retain(|e| e.run_id != entry.run_id);
+        let rank = entries.iter().position(|e| entry.score > e.score).unwrap_or(entries.len());
+        if rank >= ENTRIES_PER_TABLE {
+            return None;
+        }
+        entries.insert(rank, entry);
+        entries.truncate(ENTRIES_PER_TABLE);
+        Some(rank)
+    }
+}
+
+fn create_resources(
+    mut commands: Commands,
+    saves: Res<Saves>,
+) {
+    let high_scores: HighScores = saves.load(SAVE_KEY).unwrap_or_default();
+    commands.insert_resource(high_scores);
+}
+
+fn record_run(
+    mut run_ended_reader: EventReader<RunEnded>,
+    campaign: Res<Campaign>,
+    difficulty: Res<Difficulty>,
+    mut high_scores: ResMut<HighScores>,
+    level: Res<Level>,
+    mode: Res<Mode>,
+    run_rng: Res<RunRng>,
+    saves: Res<Saves>,
+    score: Res<Score>,
+) {
+    for run_ended in run_ended_reader.iter() {
+        let entry = Entry {
+            run_id: run_rng.seed,
+            score: score.points,
+            unix_secs: save::now_unix_secs(),
+            won: run_ended.won,
+            stage_reached: level.stage + 1,
+            max_combo: score.max_combo,
+            hits: score.hits,
+            damage_taken: score.damage_taken,
+            continues_used: score.continues_used,
+        };
+        let rank = high_scores.insert(campaign.song(0, *mode), *difficulty, *mode, entry);
+        log::info!("Run ended with score {}, high score rank {:?}", score.points, rank);
+        if rank.is_some() {
+            saves.save(SAVE_KEY, &*high_scores);
+        }
+    }
+}
+
+fn spawn_text(
+    mut commands: Commands,
+    campaign: Res<Campaign>,
+    difficulty: Res<Difficulty>,
+    fonts: Res<Fonts>,
+    high_scores: Res<HighScores>,
+) {
+    let entries = high_scores.entries(campaign.stages[0].song, *difficulty, Mode::Campaign);
+    if entries.is_empty() {
+        return;
+    }
+
+    let mut lines = vec!["High scores".to_owned()];
+    for (i, entry) in entries.iter().take(ENTRIES_ON_MENU).enumerate() {
+        lines.push(format!("{}. {}  {}  {}",
+                           i + 1, entry.score, save::format_date(entry.unix_secs),
+                           if entry.won {
+                               "Won".to_owned()
+                           } else {
+                               format!("Level {}", entry.stage_reached)
+                           }));
+    }
+
+    commands.spawn_bundle(Text2dBundle {
+        text: Text::with_section(
+            lines.join("\n"),
+            TextStyle {
+                font: fonts.fira_sans.clone(),
+                font_size: 20.,
+                color: Color::WHITE,
+            },
+            TextAlignment {
+                vertical: VerticalAlign::Top,
+                horizontal: HorizontalAlign::Center,
+            }),
+        transform: Transform {
+            translation: Vec3::new(0., 290., 10.),
+            .. Default::default()
+        },
+        .. Default::default()
+    }).insert(Anchored::new(Anchor::Top))
+      .insert(HighScoresText);
+}
+
+fn cleanup(
+    mut commands: Commands,
+    query: Query<Entity, With<HighScoresText>>,
+) {
+    for ent in query.iter() {
+        commands.entity(ent).despawn();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(run_id: u64, score: u32) -> Entry {
+        Entry {
+            run_id,
+            score,
+            unix_secs: 0,
+            won: false,
+            stage_reached: 1,
+            max_combo: 0,
+            hits: 0,
+            damage_taken: 0,
+            continues_used: 0,
+        }
+    }
+
+    fn insert(high_scores: &mut HighScores, entry: Entry) -> Option<usize> {
+        high_scores.insert(Song::Ninja, Difficulty::Normal, Mode::Campaign, entry)
+    }
+
+    fn scores(high_scores: &HighScores) -> Vec<u32> {
+        high_scores.entries(Song::Ninja, Difficulty::Normal, Mode::Campaign).iter()
+            .map(|e| e.score)
+            .collect()
+    }
+
+    #[test]
+    fn insert_sorts_best_first() {
+        let mut high_scores = HighScores::default();
+        assert_eq!(insert(&mut high_scores, entry(1, 20)), Some(0));
+        assert_eq!(insert(&mut high_scores, entry(2, 30)), Some(0));
+        assert_eq!(insert(&mut high_scores, entry(3, 10)), Some(2));
+        assert_eq!(insert(&mut high_scores, entry(4, 25)), Some(1));
+        assert_eq!(scores(&high_scores), vec![30, 25, 20, 10]);
+    }
+
+    #[test]
+    fn insert_ties_rank_below_older_entries() {
+        let mut high_scores = HighScores::default();
+        insert(&mut high_scores, entry(1, 20));
+        assert_eq!(insert(&mut high_scores, entry(2, 20)), Some(1));
+    }
+
+    #[test]
+    fn insert_truncates_the_table() {
+        let mut high_scores = HighScores::default();
+        for i in 0..ENTRIES_PER_TABLE as u32 {
+            insert(&mut high_scores, entry(i as u64 + 1, 100 - 2 * i));
+        }
+        assert_eq!(insert(&mut high_scores, entry(100, 1)), None);
+        assert_eq!(insert(&mut high_scores, entry(101, 95)), Some(3));
+        let scores = scores(&high_scores);
+        assert_eq!(scores.len(), ENTRIES_PER_TABLE);
+        assert_eq!(scores[3], 95);
+        assert_eq!(*scores.last().unwrap(), 84);
+    }
+
+    #[test]
+    fn insert_replaces_the_same_run() {
+        let mut high_scores = HighScores::default();
+        insert(&mut high_scores, entry(1, 50));
+        insert(&mut high_scores, entry(2, 40));
+        assert_eq!(insert(&mut high_scores, entry(2, 60)), Some(0));
+        assert_eq!(scores(&high_scores), vec![60, 50]);
+    }
+
+    #[test]
+    fn tables_are_separate() {
+        let mut high_scores = HighScores::default();
+        insert(&mut high_scores, entry(1, 50));
+        high_scores.insert(Song::Ninja, Difficulty::Hard, Mode::Campaign, entry(2, 10));
+        assert_eq!(scores(&high_scores), vec![50]);
+        assert_eq!(high_scores.entries(Song::Ninja, Difficulty::Hard, Mode::Campaign).len(), 1);
+    }
+}
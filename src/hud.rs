@@ -0,0 +1,255 @@
+//! The heads-up display during a fight: score, combo, accuracy, the HP of each fighter and,
+//! if enabled, what the enemy is vulnerable to. Judgements are floating text.
+//!
+//! Each part is only redrawn when an event says it may have changed, rather than every frame.
+//! HP and weaknesses are shown on each health bar.
+
+use bevy::prelude::*;
+use crate::{
+    components::{Enemy, Health},
+    events::{ActionTimed, DamageApplied, DamageBlocked, Die, Healed},
+    game_state::GameState,
+    health_bar::HealthBar,
+    layout::{Anchor, Anchored},
+    loading::Fonts,
+    resources::{RunStart, Score},
+    settings::Settings,
+    types::Judgement,
+};
+
+pub struct Plugin;
+
+struct HudEntity;
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum HudText {
+    Score,
+    Combo,
+    Accuracy,
+}
+
+/// The HP of a health bar's owner, as a child of the bar.
+struct HpText;
+
+/// Above an enemy's health bar.
+const WEAKNESSES_Y: f32 = 31.;
+
+/// Presses of the button this run, for the accuracy.
+#[derive(Default)]
+struct Accuracy {
+    hits: u32,
+    presses: u32,
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .insert_resource(Accuracy::default())
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(spawn_hud.system().after("reset_score")))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(update_score.system().after("update_score"))
+                    .with_system(update_accuracy.system())
+                    .with_system(add_bar_texts.system()))
+            // The shop heals while Playing is paused underneath it.
+            .add_system_set(
+                SystemSet::on_in_stack_update(GameState::Playing)
+                    .with_system(update_hp.system()))
+            ;
+    }
+}
+
+impl Accuracy {
+    fn text(&self) -> String {
+        if self.presses == 0 {
+            "Accuracy: -".to_owned()
+        } else {
+            format!("Accuracy: {:.0}%", 100. * self.hits as f32 / self.presses as f32)
+        }
+    }
+}
+
+fn spawn_hud(
+    mut commands: Commands,
+    hud_query: Query<Entity, With<HudEntity>>,
+    mut accuracy: ResMut<Accuracy>,
+    fonts: Res<Fonts>,
+    run_start: Res<RunStart>,
+    score: Res<Score>,
+) {
+    for ent in hud_query.iter() {
+        commands.entity(ent).despawn();
+    }
+
+    // A continued run keeps its accuracy along with its score.
+    if let RunStart::New = *run_start {
+        *accuracy = Accuracy::default();
+    }
+
+    // Right aligned, so they stay against the edge as the numbers grow.
+    let texts = [
+        (HudText::Score, format!("Score: {}", score.points), 22.,
+         Vec3::new(390., 260., 10.), Anchor::TopRight),
+        (HudText::Combo, combo_text(&score), 18.,
+         Vec3::new(390., 234., 10.), Anchor::TopRight),
+        (HudText::Accuracy, accuracy.text(), 18.,
+         Vec3::new(390., 210., 10.), Anchor::TopRight),
+    ];
+    for (hud_text, value, font_size, translation, anchor) in texts.iter() {
+        commands.spawn_bundle(Text2dBundle {
+            text: Text::with_section(
+                value.clone(),
+                TextStyle {
+                    font: fonts.fira_sans.clone(),
+                    font_size: *font_size,
+                    color: Color::WHITE,
+                },
+                TextAlignment {
+                    vertical: VerticalAlign::Center,
+                    horizontal: HorizontalAlign::Right,
+                }),
+            transform: Transform {
+                translation: *translation,
+                .. Default::default()
+            },
+            .. Default::default()
+        }).insert(*hud_text)
+          .insert(HudEntity)
+          .insert(Anchored::new(*anchor));
+    }
+}
+
+fn combo_text(score: &Score) -> String {
+    if score.combo < 2 {
+        String::new()
+    } else {
+        format!("Combo x{}", score.combo)
+    }
+}
+
+/// Runs after the `Score` has taken in the same events.
+fn update_score(
+    mut damage_applied_reader: EventReader<DamageApplied>,
+    mut damage_blocked_reader: EventReader<DamageBlocked>,
+    mut die_reader: EventReader<Die>,
+    mut query: Query<(&HudText, &mut Text)>,
+    score: Res<Score>,
+) {
+    let applied = damage_applied_reader.iter().count();
+    let blocked = damage_blocked_reader.iter().count();
+    let died = die_reader.iter().count();
+    if applied + blocked + died == 0 {
+        return;
+    }
+
+    for (hud_text, mut text) in query.iter_mut() {
+        match hud_text {
+            HudText::Score => text.sections[0].value = format!("Score: {}", score.points),
+            HudText::Combo => text.sections[0].value = combo_text(&score),
+            _ => {},
+        }
+    }
+}
+
+fn update_accuracy(
+    mut timed_reader: EventReader<ActionTimed>,
+    mut query: Query<(&HudText, &mut Text)>,
+    mut accuracy: ResMut<Accuracy>,
+) {
+    let mut any = false;
+    for timed in timed_reader.iter() {
+        accuracy.presses += 1;
+        if timed.judgement() != Judgement::Miss {
+            accuracy.hits += 1;
+        }
+        any = true;
+    }
+    if !any {
+        return;
+    }
+
+    for (hud_text, mut text) in query.iter_mut() {
+        if *hud_text == HudText::Accuracy {
+            text.sections[0].value = accuracy.text();
+        }
+    }
+}
+
+fn hp_text(health: &Health) -> String {
+    format!("{}/{}", health.current, health.max)
+}
+
+/// Puts the HP on each new health bar, and if enabled what an enemy is vulnerable to above
+/// its bar. The bar despawns them along with itself.
+fn add_bar_texts(
+    mut commands: Commands,
+    bar_query: Query<(Entity, &Parent), Added<HealthBar>>,
+    health_query: Query<(&Health, Option<&Enemy>)>,
+    fonts: Res<Fonts>,
+    settings: Res<Settings>,
+) {
+    let text = |value: String, y: f32| Text2dBundle {
+        text: Text::with_section(
+            value,
+            TextStyle {
+                font: fonts.fira_sans.clone(),
+                font_size: 14.,
+                color: Color::WHITE,
+            },
+            TextAlignment {
+                vertical: VerticalAlign::Center,
+                horizontal: HorizontalAlign::Center,
+            }),
+        transform: Transform::from_xyz(0., y, 0.5),
+        .. Default::default()
+    };
+
+    for (bar, owner) in bar_query.iter() {
+        let (health, enemy) = match health_query.get(owner.0) {
+            Ok(o) => o,
+            Err(_) => continue,
+        };
+        commands.entity(bar).with_children(|parent| {
+            parent.spawn_bundle(text(hp_text(health), 0.)).insert(HpText);
+            if enemy.is_some() && settings.gameplay.show_weaknesses {
+                let weaknesses: Vec<String> = health.vulnerable_to.iter()
+                    .map(|t| format!("{:?}", t))
+                    .collect();
+                parent.spawn_bundle(text(format!("Weak to {}", weaknesses.join(", ")),
+                                         WEAKNESSES_Y));
+            }
+        });
+    }
+}
+
+/// Redraws the HP on the health bars of fighters that were damaged or healed.
+fn update_hp(
+    mut damage_applied_reader: EventReader<DamageApplied>,
+    mut healed_reader: EventReader<Healed>,
+    mut text_query: Query<(&Parent, &mut Text), With<HpText>>,
+    bar_query: Query<&Parent, With<HealthBar>>,
+    health_query: Query<&Health>,
+) {
+    let targets: Vec<Entity> = damage_applied_reader.iter()
+        .map(|applied| applied.damage.target)
+        .chain(healed_reader.iter().map(|healed| healed.target))
+        .collect();
+    if targets.is_empty() {
+        return;
+    }
+
+    for (bar, mut text) in text_query.iter_mut() {
+        let owner = match bar_query.get(bar.0) {
+            Ok(owner) => owner.0,
+            Err(_) => continue,
+        };
+        if !targets.contains(&owner) {
+            continue;
+        }
+        if let Ok(health) = health_query.get(owner) {
+            text.sections[0].value = hp_text(health);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! Turns the keyboard, mouse, touch screen and gamepads into the one button.
+//!
+//! Systems read the button through `PrimaryButton`. Each press is taken by the first system
+//! that reads it, so a press that changes state doesn't carry over into the next state.
+
+use bevy::{
+    ecs::system::SystemParam,
+    input::{touch::Touches, InputSystem},
+    prelude::*,
+};
+use crate::settings::Settings;
+use std::time::Duration;
+
+pub struct Plugin;
+
+/// Presses closer together than this count as one. Some browsers send a click as well as
+/// a touch for one tap.
+const DEBOUNCE: Duration = Duration::from_millis(80);
+
+/// The one button was pressed on some device.
+pub struct PrimaryButtonPressed {
+    id: u64,
+}
+
+/// While `true`, the next key, mouse button or gamepad button pressed is bound as the one
+/// button. Escape or a tap cancels, and reserved keys are ignored.
+#[derive(Default)]
+pub struct Rebinding(pub bool);
+
+/// Keys with their own use on some screen, so they can't be the one button: menu navigation
+/// and pausing. The suspend key is reserved too.
+const RESERVED_KEYS: [KeyCode; 6] = [
+    KeyCode::Up,
+    KeyCode::Down,
+    KeyCode::Left,
+    KeyCode::Right,
+    KeyCode::Return,
+    KeyCode::Escape,
+];
+
+#[derive(Default)]
+pub struct Presses {
+    /// Id of the last press sent, presses are numbered from 1.
+    last_sent: u64,
+    /// Id of the last press taken by a system.
+    last_taken: u64,
+    last_press_time: Option<Duration>,
+    /// Whether the button is down on any device.
+    held: bool,
+}
+
+/// Reads presses of the one button.
+#[derive(SystemParam)]
+pub struct PrimaryButton<'a> {
+    press_reader: EventReader<'a, PrimaryButtonPressed>,
+    presses: ResMut<'a, Presses>,
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_event::<PrimaryButtonPressed>()
+            .insert_resource(Presses::default())
+            .insert_resource(Rebinding::default())
+            .add_system_to_stage(CoreStage::PreUpdate, read_devices.system().after(InputSystem))
+            .add_system(log_gamepads.system());
+    }
+}
+
+impl<'a> PrimaryButton<'a> {
+    /// Whether the button was pressed since this system last looked, taking the press so no
+    /// other system sees it.
+    pub fn take(&mut self) -> bool {
+        let mut pressed = false;
+        for press in self.press_reader.iter() {
+            if press.id > self.presses.last_taken {
+                self.presses.last_taken = press.id;
+                pressed = true;
+            }
+        }
+        pressed
+    }
+
+    pub fn held(&self) -> bool {
+        self.presses.held
+    }
+}
+
+fn read_devices(
+    mut press_writer: EventWriter<PrimaryButtonPressed>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut kb: ResMut<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    mut presses: ResMut<Presses>,
+    mut rebinding: ResMut<Rebinding>,
+    mut settings: ResMut<Settings>,
+    time: Res<Time>,
+    touches: Res<Touches>,
+) {
+    if rebinding.0 {
+        // .reset() the key so the screen that started rebinding doesn't see it as well
+        if kb.just_pressed(KeyCode::Escape) {
+            kb.reset(KeyCode::Escape);
+            rebinding.0 = false;
+        } else if touches.iter_just_pressed().next().is_some() {
+            rebinding.0 = false;
+        } else if let Some(key) = kb.get_just_pressed().next().copied() {
+            kb.reset(key);
+            if RESERVED_KEYS.contains(&key) || key == settings.keys.suspend {
+                log::info!("{:?} is reserved, it can't be the one button", key);
+            } else {
+                log::info!("Bound the one button to {:?}", key);
+                settings.keys.action = key;
+                rebinding.0 = false;
+            }
+        } else if let Some(button) = gamepad_buttons.get_just_pressed().next() {
+            log::info!("Bound the one button to gamepad {:?}", button.1);
+            settings.keys.gamepad_button = Some(button.1);
+            rebinding.0 = false;
+        } else if let Some(button) = mouse.get_just_pressed().next() {
+            log::info!("Bound the one button to mouse {:?}", button);
+            settings.keys.mouse_button = Some(*button);
+            rebinding.0 = false;
+        }
+        return;
+    }
+
+    let keys = &settings.keys;
+    presses.held =
+        kb.pressed(keys.action)
+        || keys.mouse_button.map(|b| mouse.pressed(b)).unwrap_or(false)
+        || (keys.touch && touches.iter().next().is_some())
+        || gamepad_buttons.get_pressed()
+               .any(|b| keys.gamepad_button.map(|t| b.1 == t).unwrap_or(true));
+
+    let pressed =
+        kb.just_pressed(keys.action)
+        || keys.mouse_button.map(|b| mouse.just_pressed(b)).unwrap_or(false)
+        || (keys.touch && touches.iter_just_pressed().next().is_some())
+        // Buttons on any pad count, so pads can come and go mid-run.
+        || gamepad_buttons.get_just_pressed()
+               .any(|b| keys.gamepad_button.map(|t| b.1 == t).unwrap_or(true));
+    if !pressed {
+        return;
+    }
+
+    let now = time.time_since_startup();
+    if let Some(last) = presses.last_press_time {
+        if now - last < DEBOUNCE {
+            return;
+        }
+    }
+    presses.last_press_time = Some(now);
+    presses.last_sent += 1;
+    press_writer.send(PrimaryButtonPressed {
+        id: presses.last_sent,
+    });
+}
+
+fn log_gamepads(
+    mut gamepad_reader: EventReader<GamepadEvent>,
+) {
+    for GamepadEvent(gamepad, event) in gamepad_reader.iter() {
+        match event {
+            GamepadEventType::Connected => log::info!("Gamepad {:?} connected", gamepad),
+            GamepadEventType::Disconnected => log::info!("Gamepad {:?} disconnected", gamepad),
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,220 @@
+//! Lays the game out on a virtual 800x600 screen, whatever size the window is.
+//!
+//! The camera is scaled so the virtual screen fits in the window. With `Scaling::Letterbox`
+//! the rest of the window is covered with black bars. With `Scaling::Fit` the scene carries
+//! on past the virtual screen, and `Anchored` entities move out to the edges of the window.
+
+use bevy::{
+    prelude::*,
+    render::camera::{Camera, CameraProjection, OrthographicProjection},
+};
+use crate::{
+    game_state::GameState,
+    settings::{Scaling, Settings},
+};
+
+pub const VIRTUAL_WIDTH: f32 = 800.;
+pub const VIRTUAL_HEIGHT: f32 = 600.;
+
+/// Thick enough to cover the widest window beside the virtual screen.
+const BAR_THICKNESS: f32 = 4000.;
+
+pub struct Plugin;
+
+/// The part of the world that's visible, updated when the window or settings change.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layout {
+    /// Window pixels per world unit.
+    pub scale: f32,
+    /// Half the width and height of the visible world.
+    pub half_size: Vec2,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Anchor {
+    Top,
+    Bottom,
+    Left,
+    Right,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Positioned relative to an edge or corner of the screen.
+///
+/// The entity's translation when it's spawned is its position on the virtual screen. It's
+/// kept the same distance from the anchor when more of the world is visible.
+pub struct Anchored {
+    anchor: Anchor,
+    virtual_position: Option<Vec2>,
+}
+
+/// A sprite the size of the virtual screen, scaled to cover everything visible.
+pub struct FillScreen;
+
+struct LetterboxBar;
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .insert_resource(Layout {
+                scale: 1.,
+                half_size: Vec2::new(VIRTUAL_WIDTH, VIRTUAL_HEIGHT) / 2.,
+            })
+            .add_system(fit_camera.system().label("fit_camera"))
+            .add_system(place_anchored.system().after("fit_camera"))
+            .add_system(fill_screen.system().after("fit_camera"))
+            .add_system(show_letterbox.system())
+            // The menu clears every entity as it starts.
+            .add_system_set(
+                SystemSet::on_enter(GameState::Menu)
+                    .with_system(spawn_letterbox.system()))
+            ;
+    }
+}
+
+impl Layout {
+    /// Converts a position in the window, from the bottom left, to the world.
+    pub fn window_to_world(&self, window: &Window, position: Vec2) -> Vec2 {
+        (position - Vec2::new(window.width(), window.height()) / 2.) / self.scale
+    }
+}
+
+impl Anchor {
+    fn direction(&self) -> Vec2 {
+        match self {
+            Anchor::Top => Vec2::new(0., 1.),
+            Anchor::Bottom => Vec2::new(0., -1.),
+            Anchor::Left => Vec2::new(-1., 0.),
+            Anchor::Right => Vec2::new(1., 0.),
+            Anchor::TopLeft => Vec2::new(-1., 1.),
+            Anchor::TopRight => Vec2::new(1., 1.),
+            Anchor::BottomLeft => Vec2::new(-1., -1.),
+            Anchor::BottomRight => Vec2::new(1., -1.),
+        }
+    }
+}
+
+impl Anchored {
+    pub fn new(anchor: Anchor) -> Anchored {
+        Anchored {
+            anchor,
+            virtual_position: None,
+        }
+    }
+}
+
+/// Scale the camera so the virtual screen fits in the window, keeping its aspect ratio.
+fn fit_camera(
+    mut camera_query: Query<(&mut Camera, &mut OrthographicProjection)>,
+    mut layout: ResMut<Layout>,
+    settings: Res<Settings>,
+    windows: Res<Windows>,
+) {
+    let window = match windows.get_primary() {
+        Some(w) => w,
+        None => return,
+    };
+    let window_size = Vec2::new(window.width(), window.height());
+    let scale = (window_size.x / VIRTUAL_WIDTH).min(window_size.y / VIRTUAL_HEIGHT);
+    if scale <= 0. {
+        // Minimised.
+        return;
+    }
+
+    let new_layout = Layout {
+        scale,
+        half_size: match settings.display.scaling {
+            Scaling::Letterbox => Vec2::new(VIRTUAL_WIDTH, VIRTUAL_HEIGHT) / 2.,
+            Scaling::Fit => window_size / (2. * scale),
+        },
+    };
+    if *layout != new_layout {
+        *layout = new_layout;
+    }
+
+    for (mut camera, mut projection) in camera_query.iter_mut() {
+        if projection.scale != 1. / scale {
+            // The projection's edges follow the window size, the scale maps them to the world.
+            projection.scale = 1. / scale;
+            camera.projection_matrix = projection.get_projection_matrix();
+        }
+    }
+}
+
+fn place_anchored(
+    mut query: Query<(&mut Anchored, &mut Transform)>,
+    layout: Res<Layout>,
+) {
+    let extra = layout.half_size - Vec2::new(VIRTUAL_WIDTH, VIRTUAL_HEIGHT) / 2.;
+    for (mut anchored, mut transform) in query.iter_mut() {
+        let translation = transform.translation;
+        let virtual_position = *anchored.virtual_position
+            .get_or_insert(Vec2::new(translation.x, translation.y));
+        let position = virtual_position + anchored.anchor.direction() * extra;
+        if position.x != translation.x || position.y != translation.y {
+            transform.translation.x = position.x;
+            transform.translation.y = position.y;
+        }
+    }
+}
+
+fn fill_screen(
+    mut query: Query<&mut Transform, With<FillScreen>>,
+    layout: Res<Layout>,
+) {
+    let scale = (layout.half_size.x * 2. / VIRTUAL_WIDTH)
+        .max(layout.half_size.y * 2. / VIRTUAL_HEIGHT);
+    for mut transform in query.iter_mut() {
+        if transform.scale.x != scale {
+            transform.scale = Vec3::new(scale, scale, 1.);
+        }
+    }
+}
+
+fn spawn_letterbox(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    settings: Res<Settings>,
+) {
+    let material = materials.add(Color::BLACK.into());
+    let half = Vec2::new(VIRTUAL_WIDTH, VIRTUAL_HEIGHT) / 2.;
+    let offset = BAR_THICKNESS / 2.;
+    let bars = [
+        (Vec2::new(-half.x - offset, 0.), Vec2::new(BAR_THICKNESS, BAR_THICKNESS * 2.)),
+        (Vec2::new(half.x + offset, 0.), Vec2::new(BAR_THICKNESS, BAR_THICKNESS * 2.)),
+        (Vec2::new(0., half.y + offset), Vec2::new(VIRTUAL_WIDTH, BAR_THICKNESS)),
+        (Vec2::new(0., -half.y - offset), Vec2::new(VIRTUAL_WIDTH, BAR_THICKNESS)),
+    ];
+    for (position, size) in bars.iter() {
+        commands.spawn_bundle(SpriteBundle {
+            material: material.clone(),
+            sprite: Sprite::new(*size),
+            transform: Transform {
+                // Above everything else in the scene.
+                translation: Vec3::new(position.x, position.y, 100.),
+                .. Default::default()
+            },
+            visible: Visible {
+                is_visible: settings.display.scaling == Scaling::Letterbox,
+                is_transparent: true,
+            },
+            .. Default::default()
+        }).insert(LetterboxBar);
+    }
+}
+
+fn show_letterbox(
+    mut query: Query<&mut Visible, With<LetterboxBar>>,
+    settings: Res<Settings>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    for mut visible in query.iter_mut() {
+        visible.is_visible = settings.display.scaling == Scaling::Letterbox;
+    }
+}
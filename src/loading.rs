@@ -1,4 +1,9 @@
-use crate::game_state::GameState;
+use crate::{
+    achievements::Achievements,
+    campaign::Campaign,
+    credits::Credits,
+    game_state::GameState,
+};
 use bevy::prelude::*;
 use bevy_asset_loader::{AssetCollection, AssetLoader};
 use bevy_kira_audio::AudioSource;
@@ -16,6 +21,7 @@ impl bevy::prelude::Plugin for Plugin {
             .with_collection::<Sounds>()
             .with_collection::<TextureAssets>()
             .with_collection::<CountdownTextures>()
+            .with_collection::<Data>()
             .build(app);
     }
 }
@@ -79,6 +85,8 @@ pub struct TextureAssets {
     pub icon_arrow_highlight: Handle<Texture>,
     #[asset(path = "sprites/david_dawn/pointer.png")]
     pub icon_pointer: Handle<Texture>,
+    #[asset(path = "sprites/david_dawn/orb_purple.png")]
+    pub ghost_marker: Handle<Texture>,
 
     #[asset(path = "sprites/lpc-medieval-fantasy-character/our_work/archer/walk_down/00.png")]
     pub archer_idle: Handle<Texture>,
@@ -121,11 +129,6 @@ pub struct TextureAssets {
     #[asset(path = "sprites/lpc-medieval-fantasy-character/our_work/player/die/05.png")]
     pub player_dead: Handle<Texture>,
 
-    #[asset(path = "sprites/david_dawn/hit.png")]
-    pub damage_hit: Handle<Texture>,
-    #[asset(path = "sprites/david_dawn/blocked.png")]
-    pub damage_blocked: Handle<Texture>,
-
     #[asset(path = "sprites/david_dawn/blood.png")]
     pub blood_splatter: Handle<Texture>,
     #[asset(path = "sprites/david_dawn/shield_flash_sheet.png")]
@@ -146,8 +149,6 @@ pub struct TextureAssets {
 
     #[asset(path = "sprites/david_dawn/menu_text.png")]
     pub menu_text: Handle<Texture>,
-    #[asset(path = "sprites/david_dawn/click_here.png")]
-    pub menu_click_here: Handle<Texture>,
 
     #[asset(path = "sprites/david_dawn/boss_text_sheet.png")]
     pub boss_text_sheet: Handle<Texture>,
@@ -182,3 +183,13 @@ pub struct CountdownTextures {
     #[asset(path = "sprites/david_dawn/countdown/fight.png")]
     pub fight: Handle<ColorMaterial>,
 }
+
+#[derive(AssetCollection)]
+pub struct Data {
+    #[asset(path = "main.campaign.ron")]
+    pub campaign: Handle<Campaign>,
+    #[asset(path = "main.achievements.ron")]
+    pub achievements: Handle<Achievements>,
+    #[asset(path = "sources.md")]
+    pub credits: Handle<Credits>,
+}
@@ -0,0 +1,197 @@
+//! Shows how many assets have loaded while `GameState::Loading` waits for them.
+//!
+//! `bevy_asset_loader` waits forever if an asset fails, so the load state of every asset in
+//! the collections is checked here too, and any failure goes to `GameState::LoadingFailed`
+//! with the failing paths listed.
+
+use bevy::{
+    asset::LoadState,
+    prelude::*,
+};
+use bevy_asset_loader::AssetCollection;
+use crate::{
+    game_state::GameState,
+    loading::{CountdownTextures, Data, Fonts, Sounds, TextureAssets},
+};
+
+pub struct Plugin;
+
+/// The handles `bevy_asset_loader` is waiting for.
+struct LoadingHandles {
+    handles: Vec<HandleUntyped>,
+}
+
+struct LoadingEntity;
+
+struct ProgressText;
+
+struct ProgressBar;
+
+/// The paths of assets that failed to load.
+struct FailedPaths(Vec<String>);
+
+/// The loading screen is shown before `Fonts` is loaded, so it loads its font itself.
+const FONT_PATH: &str = "fonts/FiraSans-Bold.ttf";
+const BAR_WIDTH: f32 = 300.;
+const BAR_HEIGHT: f32 = 16.;
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_system_set(
+                SystemSet::on_enter(GameState::Loading)
+                    .with_system(start_loading.exclusive_system())
+                    .with_system(spawn_screen.system()))
+            .add_system_set(
+                SystemSet::on_update(GameState::Loading)
+                    .with_system(update_progress.system()))
+            .add_system_set(
+                SystemSet::on_exit(GameState::Loading)
+                    .with_system(cleanup.system()))
+            .add_system_set(
+                SystemSet::on_enter(GameState::LoadingFailed)
+                    .with_system(spawn_error.system()))
+            ;
+    }
+}
+
+/// Ask for the same handles as `bevy_asset_loader`. Assets already loading aren't loaded
+/// twice.
+fn start_loading(world: &mut World) {
+    let mut handles = Fonts::load(world);
+    handles.extend(Sounds::load(world));
+    handles.extend(TextureAssets::load(world));
+    handles.extend(CountdownTextures::load(world));
+    handles.extend(Data::load(world));
+    world.insert_resource(LoadingHandles { handles });
+}
+
+fn spawn_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands.spawn_bundle(OrthographicCameraBundle::new_2d())
+        .insert(LoadingEntity);
+
+    commands.spawn_bundle(Text2dBundle {
+        text: Text::with_section(
+            "Loading",
+            TextStyle {
+                font: asset_server.load(FONT_PATH),
+                font_size: 20.,
+                color: Color::WHITE,
+            },
+            TextAlignment {
+                vertical: VerticalAlign::Center,
+                horizontal: HorizontalAlign::Center,
+            }),
+        transform: Transform {
+            translation: Vec3::new(0., 30., 10.),
+            .. Default::default()
+        },
+        .. Default::default()
+    }).insert(ProgressText)
+      .insert(LoadingEntity);
+
+    commands.spawn_bundle(SpriteBundle {
+        material: materials.add(Color::rgb(0.2, 0.2, 0.2).into()),
+        sprite: Sprite::new(Vec2::new(BAR_WIDTH, BAR_HEIGHT)),
+        transform: Transform {
+            translation: Vec3::new(0., 0., 10.),
+            .. Default::default()
+        },
+        .. Default::default()
+    }).insert(LoadingEntity);
+
+    commands.spawn_bundle(SpriteBundle {
+        material: materials.add(Color::rgb(242./255., 0., 48./255.).into()),
+        sprite: Sprite::new(Vec2::new(0., BAR_HEIGHT)),
+        transform: Transform {
+            translation: Vec3::new(0., 0., 11.),
+            .. Default::default()
+        },
+        .. Default::default()
+    }).insert(ProgressBar)
+      .insert(LoadingEntity);
+}
+
+fn update_progress(
+    mut commands: Commands,
+    mut bar_query: Query<(&mut Sprite, &mut Transform), With<ProgressBar>>,
+    mut text_query: Query<&mut Text, With<ProgressText>>,
+    asset_server: Res<AssetServer>,
+    loading_handles: Res<LoadingHandles>,
+    mut state: ResMut<State<GameState>>,
+) {
+    let total = loading_handles.handles.len();
+    let mut loaded = 0;
+    let mut failed = Vec::new();
+    for handle in loading_handles.handles.iter() {
+        match asset_server.get_load_state(handle) {
+            LoadState::Loaded => loaded += 1,
+            LoadState::Failed => {
+                let path = asset_server.get_handle_path(handle)
+                    .map(|p| p.path().display().to_string())
+                    .unwrap_or_else(|| format!("{:?}", handle.id));
+                failed.push(path);
+            },
+            _ => {},
+        }
+    }
+
+    if !failed.is_empty() {
+        for path in failed.iter() {
+            log::error!("Failed to load asset: {}", path);
+        }
+        commands.insert_resource(FailedPaths(failed));
+        state.set(GameState::LoadingFailed).unwrap();
+        return;
+    }
+
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = format!("Loading {}/{}", loaded, total);
+    }
+    let width = BAR_WIDTH * loaded as f32 / total.max(1) as f32;
+    for (mut sprite, mut transform) in bar_query.iter_mut() {
+        sprite.size.x = width;
+        transform.translation.x = (width - BAR_WIDTH) / 2.;
+    }
+}
+
+fn spawn_error(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    failed: Res<FailedPaths>,
+) {
+    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
+
+    // If the font itself failed nothing shows, but the paths are logged as well.
+    commands.spawn_bundle(Text2dBundle {
+        text: Text::with_section(
+            format!("Failed to load:\n{}", failed.0.join("\n")),
+            TextStyle {
+                font: asset_server.load(FONT_PATH),
+                font_size: 20.,
+                color: Color::WHITE,
+            },
+            TextAlignment {
+                vertical: VerticalAlign::Top,
+                horizontal: HorizontalAlign::Center,
+            }),
+        transform: Transform {
+            translation: Vec3::new(0., 250., 10.),
+            .. Default::default()
+        },
+        .. Default::default()
+    });
+}
+
+fn cleanup(
+    mut commands: Commands,
+    query: Query<Entity, With<LoadingEntity>>,
+) {
+    for ent in query.iter() {
+        commands.entity(ent).despawn();
+    }
+}
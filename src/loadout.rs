@@ -0,0 +1,246 @@
+use bevy::prelude::*;
+use crate::{
+    components::Action,
+    game_state::GameState,
+    input::PrimaryButton,
+    loading::Fonts,
+    resources::{Loadout, RunStart},
+    settings::Settings,
+};
+use std::time::Duration;
+
+/// Lets the player choose the action for each slot of the action spinner before a run.
+/// The action offered for the current slot cycles by itself, pressing the button picks it.
+/// The bottom slot is always Defend, so every loadout can block the enemy's attacks.
+pub struct Plugin;
+
+struct LoadoutEntity;
+
+struct SlotText {
+    slot: usize,
+}
+
+struct Picker {
+    /// Slot currently being chosen.
+    slot: usize,
+    /// Index into `POOL` of the action offered for the current slot.
+    choice: usize,
+    next_cycle: Duration,
+}
+
+static POOL: [Action; 7] = [
+    Action::AttackSword,
+    Action::AttackMagic,
+    Action::AttackArrow,
+    Action::Defend,
+    Action::Heal,
+    Action::AttackDoubleArrow,
+    Action::AttackRay,
+];
+
+const SLOT_NAMES: [&str; 4] = ["Top", "Left", "Bottom", "Right"];
+/// Enemy attacks land just before the pointer reaches the bottom.
+const DEFEND_SLOT: usize = 2;
+
+const CYCLE_PERIOD: Duration = Duration::from_millis(900);
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_system_set(
+                SystemSet::on_enter(GameState::CreateResources)
+                    .with_system(create_resources.system()))
+            .add_system_set(
+                SystemSet::on_enter(GameState::Loadout)
+                    .with_system(on_enter.system()))
+            .add_system_set(
+                SystemSet::on_update(GameState::Loadout)
+                    .with_system(cycle_choice.system())
+                    .with_system(keyboard_input.system())
+                    .with_system(update_text.system()))
+            .add_system_set(
+                SystemSet::on_exit(GameState::Loadout)
+                    .with_system(cleanup.system()))
+            ;
+    }
+}
+
+fn create_resources(
+    mut commands: Commands,
+) {
+    commands.insert_resource(Loadout::default());
+    commands.insert_resource(Picker {
+        slot: 0,
+        choice: 0,
+        next_cycle: Duration::default(),
+    });
+}
+
+fn pool_index(action: &Action) -> usize {
+    POOL.iter().position(|a| a == action).unwrap_or(0)
+}
+
+/// The slot to pick after `slot`, skipping the Defend slot. `None` once they're all picked.
+fn next_slot(slot: Option<usize>, loadout: &Loadout) -> Option<usize> {
+    let start = slot.map(|s| s + 1).unwrap_or(0);
+    (start..loadout.slots.len()).find(|&s| s != DEFEND_SLOT)
+}
+
+fn on_enter(
+    mut commands: Commands,
+    fonts: Res<Fonts>,
+    mut loadout: ResMut<Loadout>,
+    mut picker: ResMut<Picker>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+) {
+    loadout.slots[DEFEND_SLOT] = Action::Defend;
+
+    // Start each slot on its current action, so a few quick presses keep the old loadout.
+    let slot = next_slot(None, &loadout).unwrap();
+    *picker = Picker {
+        slot,
+        choice: pool_index(&loadout.slots[slot]),
+        next_cycle: time.time_since_startup() + CYCLE_PERIOD,
+    };
+
+    let alignment = TextAlignment {
+        vertical: VerticalAlign::Center,
+        horizontal: HorizontalAlign::Center,
+    };
+
+    commands.spawn_bundle(Text2dBundle {
+        text: Text::with_section(
+            "Loadout",
+            TextStyle {
+                font: fonts.fiendish.clone(),
+                font_size: 60.,
+                color: Color::rgb(242./255., 0., 48./255.),
+            },
+            alignment),
+        transform: Transform {
+            translation: Vec3::new(0., 200., 10.),
+            .. Default::default()
+        },
+        .. Default::default()
+    }).insert(LoadoutEntity);
+
+    for slot in 0..SLOT_NAMES.len() {
+        commands.spawn_bundle(Text2dBundle {
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: fonts.fira_sans.clone(),
+                    font_size: 30.,
+                    color: Color::WHITE,
+                },
+                alignment),
+            transform: Transform {
+                translation: Vec3::new(0., 90. - 50. * (slot as f32), 10.),
+                .. Default::default()
+            },
+            .. Default::default()
+        }).insert(SlotText { slot })
+          .insert(LoadoutEntity);
+    }
+
+    commands.spawn_bundle(Text2dBundle {
+        text: Text::with_section(
+            settings.keys.prompt("choose"),
+            TextStyle {
+                font: fonts.fira_sans.clone(),
+                font_size: 20.,
+                color: Color::WHITE,
+            },
+            alignment),
+        transform: Transform {
+            translation: Vec3::new(0., -200., 10.),
+            .. Default::default()
+        },
+        .. Default::default()
+    }).insert(LoadoutEntity);
+}
+
+fn cycle_choice(
+    mut picker: ResMut<Picker>,
+    time: Res<Time>,
+) {
+    let now = time.time_since_startup();
+    if now >= picker.next_cycle {
+        picker.choice = (picker.choice + 1) % POOL.len();
+        picker.next_cycle = now + CYCLE_PERIOD;
+    }
+}
+
+fn keyboard_input(
+    mut button: PrimaryButton,
+    mut loadout: ResMut<Loadout>,
+    mut picker: ResMut<Picker>,
+    mut run_start: ResMut<RunStart>,
+    mut state: ResMut<State<GameState>>,
+    time: Res<Time>,
+) {
+    if !button.take() {
+        return;
+    }
+
+    let slot = picker.slot;
+    loadout.slots[slot] = POOL[picker.choice].clone();
+    debug!("loadout: slot {} = {:?}", slot, loadout.slots[slot]);
+
+    let next = match next_slot(Some(slot), &loadout) {
+        Some(next) => next,
+        None => {
+            *run_start = RunStart::New;
+            state.set(GameState::Playing).unwrap();
+            return;
+        },
+    };
+
+    *picker = Picker {
+        slot: next,
+        choice: pool_index(&loadout.slots[next]),
+        next_cycle: time.time_since_startup() + CYCLE_PERIOD,
+    };
+}
+
+fn update_text(
+    mut query: Query<(&SlotText, &mut Text)>,
+    loadout: Res<Loadout>,
+    picker: Res<Picker>,
+    settings: Res<Settings>,
+) {
+    let colorblind = settings.accessibility.colorblind_icons;
+    for (slot_text, mut text) in query.iter_mut() {
+        let slot = slot_text.slot;
+        let section = &mut text.sections[0];
+        let action =
+            if slot == picker.slot {
+                &POOL[picker.choice]
+            } else {
+                &loadout.slots[slot]
+            };
+        section.value = format!("{}: {}", SLOT_NAMES[slot], action.name());
+        // The slot being chosen is only shown by its colour otherwise.
+        if colorblind && slot == picker.slot {
+            section.value = format!("> {} <", section.value);
+        }
+        section.style.color =
+            if slot == picker.slot {
+                Color::rgb(242./255., 0., 48./255.)
+            } else {
+                Color::WHITE
+            };
+    }
+}
+
+fn cleanup(
+    mut commands: Commands,
+    query: Query<Entity, With<LoadoutEntity>>,
+) {
+    for ent in query.iter() {
+        commands
+            .entity(ent)
+            .despawn();
+    }
+}
@@ -1,22 +1,50 @@
+mod achievements;
 mod action_spinner;
+mod bestiary;
+mod campaign;
 mod components;
+mod credits;
 mod enemy;
 mod events;
 mod fight_display;
+mod floating_text;
 mod game_over;
 mod game_state;
+mod ghost;
+mod health_bar;
+mod high_scores;
+mod hud;
+mod input;
+mod layout;
 mod loading;
+mod loading_screen;
+mod loadout;
 mod music;
+mod navigation;
+mod options;
+mod pause;
 mod player;
+mod replay;
 mod resources;
+mod save;
+mod score;
+mod settings;
+mod shop;
+mod song_select;
+mod stats;
+mod suspend;
 mod systems;
 mod types;
 
 use bevy::prelude::*;
 use bevy_kira_audio::AudioPlugin;
 use crate::{
-    resources::Icons,
+    campaign::{Backdrop, Campaign},
+    resources::{Icons, Level, Mode},
     game_state::GameState,
+    layout::FillScreen,
+    save::Saves,
+    settings::Settings,
 };
 
 #[cfg(feature = "diagnostics")]
@@ -35,12 +63,13 @@ use {
 use bevy::wgpu::diagnostic::WgpuResourceDiagnosticsPlugin;
 
 struct Background {
-    screen: Handle<ColorMaterial>,
+    day: Handle<ColorMaterial>,
+    night: Handle<ColorMaterial>,
+    inferno: Handle<ColorMaterial>,
     platform: Handle<ColorMaterial>,
 }
 
-const WIN_W: f32 = 800.;
-const WIN_H: f32 = 600.;
+struct BackgroundScreen;
 
 fn main() {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn"))
@@ -49,33 +78,63 @@ fn main() {
 
     log::info!("main()");
 
+    let saves = Saves::new();
+    let settings = Settings::load(&saves);
+
     let mut app = App::build();
 
     app
-        .insert_resource(WindowDescriptor {
-            title: "One-Click Ninja".to_string(),
-            width: WIN_W,
-            height: WIN_H,
-            vsync: true, //Doesn't actually work (at least on linux)
-            .. Default::default()
-        })
+        .insert_resource(settings.window_descriptor())
+        .insert_resource(saves)
+        .insert_resource(settings.gameplay.difficulty)
+        .insert_resource(settings)
+        .insert_resource(Mode::Campaign)
+        .add_event::<events::ActionTimed>()
+        .add_event::<events::ButtonPressed>()
         .add_event::<events::Damage>()
         .add_event::<events::DamageApplied>()
+        .add_event::<events::DamageBlocked>()
         .add_event::<events::Die>()
+        .add_event::<events::EnemyAttackOutcome>()
         .add_event::<events::EnemyAttackTime>()
+        .add_event::<events::Healed>()
         .add_event::<events::MusicTime>()
         .add_event::<events::PlayerAttackAction>()
         .add_event::<events::PlayerDefendAction>()
+        .add_event::<events::PlayerHealAction>()
+        .add_event::<events::RunEnded>()
         .add_plugins(DefaultPlugins)
         .add_state(GameState::Loading)
         .add_plugin(AudioPlugin)
+        .add_plugin(achievements::Plugin)
+        .add_plugin(bestiary::Plugin)
+        .add_plugin(campaign::Plugin)
+        .add_plugin(credits::Plugin)
         .add_plugin(loading::Plugin)
+        .add_plugin(loading_screen::Plugin)
         .add_plugin(action_spinner::Plugin)
         .add_plugin(enemy::Plugin)
         .add_plugin(fight_display::Plugin)
+        .add_plugin(floating_text::Plugin)
         .add_plugin(game_over::Plugin)
+        .add_plugin(ghost::Plugin)
+        .add_plugin(health_bar::Plugin)
+        .add_plugin(high_scores::Plugin)
+        .add_plugin(hud::Plugin)
+        .add_plugin(input::Plugin)
+        .add_plugin(layout::Plugin)
+        .add_plugin(loadout::Plugin)
         .add_plugin(music::Plugin)
+        .add_plugin(options::Plugin)
+        .add_plugin(pause::Plugin)
         .add_plugin(player::Plugin)
+        .add_plugin(replay::Plugin)
+        .add_plugin(score::Plugin)
+        .add_plugin(settings::Plugin)
+        .add_plugin(shop::Plugin)
+        .add_plugin(song_select::Plugin)
+        .add_plugin(stats::Plugin)
+        .add_plugin(suspend::Plugin)
         .add_plugin(systems::animate_sprite_sheet::Plugin)
         .add_plugin(systems::countdown::Plugin)
         .add_plugin(systems::damage::Plugin)
@@ -91,6 +150,9 @@ fn main() {
         .add_system_set(
             SystemSet::on_enter(GameState::Playing)
                 .with_system(on_enter_playing.system()))
+        .add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(update_backdrop.system()))
         ;
 
     #[cfg(feature = "diagnostics")]
@@ -121,8 +183,13 @@ fn create_resources(
         defend: materials.add(texture_assets.icon_shield.clone().into()),
     });
 
+    let background = &texture_assets.background;
     commands.insert_resource(Background {
-        screen: materials.add(texture_assets.background.clone().into()),
+        day: materials.add(background.clone().into()),
+        night: materials.add(ColorMaterial::modulated_texture(background.clone(),
+                                                              Color::rgb(0.4, 0.45, 0.7))),
+        inferno: materials.add(ColorMaterial::modulated_texture(background.clone(),
+                                                                Color::rgb(1., 0.45, 0.35))),
         platform: materials.add(texture_assets.platform.clone().into()),
     });
 }
@@ -134,13 +201,34 @@ fn setup(
     commands.spawn_bundle(OrthographicCameraBundle::new_2d());
 
     commands.spawn_bundle(SpriteBundle {
-        material: background.screen.clone(),
+        material: background.day.clone(),
         transform: Transform {
             translation: Vec3::new(0., 0., 0.),
             .. Default::default()
         },
         .. Default::default()
-    });
+    }).insert(BackgroundScreen)
+      .insert(FillScreen);
+}
+
+/// Switch the background to the backdrop of the current campaign stage.
+fn update_backdrop(
+    mut screen: Query<&mut Handle<ColorMaterial>, With<BackgroundScreen>>,
+    background: Res<Background>,
+    campaign: Res<Campaign>,
+    level: Res<Level>,
+) {
+    if !level.is_changed() {
+        return;
+    }
+
+    for mut material in screen.iter_mut() {
+        *material = match campaign.stages[level.stage].backdrop {
+            Backdrop::Day => background.day.clone(),
+            Backdrop::Night => background.night.clone(),
+            Backdrop::Inferno => background.inferno.clone(),
+        };
+    }
 }
 
 fn on_enter_playing(
@@ -1,20 +1,33 @@
 use bevy::prelude::*;
-use bevy_kira_audio::{Audio, AudioSource};
+use bevy_kira_audio::{Audio, AudioChannel, AudioSource};
 use crate::{
+    campaign::{Campaign, Song},
     events::MusicTime,
     game_state::GameState,
     loading,
+    resources::{Level, Mode},
+    settings::Settings,
 };
 
 pub struct Plugin;
 
+/// Music plays in its own channel so its volume can be set separately from sound effects,
+/// which use the default channel.
+pub struct Channels {
+    pub music: AudioChannel,
+}
+
 struct Sounds {
     playing_loop: TrackSettings,
 }
 
 struct MusicInstance {
     instance: bevy_kira_audio::InstanceHandle,
+    song: Song,
     track: TrackSettings,
+    /// Bars played, which keeps counting when the track loops.
+    bars: u32,
+    last_beat_in_bar: Option<f64>,
 }
 
 #[derive(Clone)]
@@ -27,18 +40,32 @@ struct TrackSettings {
 impl bevy::app::Plugin for Plugin {
     fn build(&self, app: &mut AppBuilder) {
         app
+            .insert_resource(Channels {
+                music: AudioChannel::new("music".to_owned()),
+            })
             .add_system_set(
                 SystemSet::on_enter(GameState::CreateResources)
                     .with_system(create_resources.system()))
             .add_system_set(
                 SystemSet::on_enter(GameState::Playing)
-                    .with_system(start_music.system()))
+                    .with_system(start_music.system().after("set_level")))
             .add_system_set(
                 SystemSet::on_update(GameState::Playing)
-                    .with_system(on_update.system()))
+                    .with_system(on_update.system())
+                    .with_system(change_song.system()))
             .add_system_set(
                 SystemSet::on_exit(GameState::Playing)
                     .with_system(stop_music.system()))
+            // The options screen plays music to test the latency offset against.
+            .add_system_set(
+                SystemSet::on_enter(GameState::Options)
+                    .with_system(start_test_music.system()))
+            .add_system_set(
+                SystemSet::on_update(GameState::Options)
+                    .with_system(on_update.system()))
+            .add_system_set(
+                SystemSet::on_exit(GameState::Options)
+                    .with_system(stop_music.system()))
             ;
     }
 }
@@ -56,34 +83,106 @@ fn create_resources(
     });
 }
 
+impl Sounds {
+    fn track(&self, song: Song) -> &TrackSettings {
+        match song {
+            Song::Ninja => &self.playing_loop,
+        }
+    }
+}
+
 fn start_music(
     mut commands: Commands,
     audio: Res<Audio>,
+    campaign: Res<Campaign>,
+    channels: Res<Channels>,
+    level: Res<Level>,
+    mode: Res<Mode>,
+    sounds: Res<Sounds>,
+) {
+    let song = campaign.song(level.stage, *mode);
+    let track = sounds.track(song);
+    let instance = audio.play_looped_in_channel(track.audio.clone(), &channels.music);
+    commands.insert_resource(MusicInstance {
+        instance,
+        song,
+        track: track.clone(),
+        bars: 0,
+        last_beat_in_bar: None,
+    });
+}
+
+fn start_test_music(
+    mut commands: Commands,
+    audio: Res<Audio>,
+    channels: Res<Channels>,
     sounds: Res<Sounds>,
 ) {
-    let track = &sounds.playing_loop;
-    let instance = audio.play_looped(track.audio.clone());
+    let song = Song::ALL[0];
+    let track = sounds.track(song);
+    let instance = audio.play_looped_in_channel(track.audio.clone(), &channels.music);
     commands.insert_resource(MusicInstance {
         instance,
+        song,
         track: track.clone(),
+        bars: 0,
+        last_beat_in_bar: None,
     });
 }
 
+/// Switch to the song of the current campaign stage, if it's different. The bar count
+/// carries on, so `MusicTime::song_beat` keeps counting up across the switch.
+fn change_song(
+    audio: Res<Audio>,
+    campaign: Res<Campaign>,
+    channels: Res<Channels>,
+    level: Res<Level>,
+    mode: Res<Mode>,
+    mut music_instance: ResMut<MusicInstance>,
+    sounds: Res<Sounds>,
+) {
+    if !level.is_changed() {
+        return;
+    }
+
+    let song = campaign.song(level.stage, *mode);
+    if song == music_instance.song {
+        return;
+    }
+
+    audio.stop_channel(&channels.music);
+    let track = sounds.track(song);
+    music_instance.instance = audio.play_looped_in_channel(track.audio.clone(), &channels.music);
+    music_instance.song = song;
+    music_instance.track = track.clone();
+}
+
 fn on_update(
     mut music_time_writer: EventWriter<MusicTime>,
     audio: Res<Audio>,
-    music_instance: Res<MusicInstance>,
+    mut music_instance: ResMut<MusicInstance>,
+    settings: Res<Settings>,
 ) {
     let track = music_instance.track.clone();
     let pos = audio.state(music_instance.instance.clone()).position();
     if let Some(pos) = pos {
+        // The player hears the music at `pos` a little later than it's played.
+        let pos = pos - settings.audio.latency_offset_secs();
         let beat_secs = 60. / track.bpm;
         let bar_secs = beat_secs * 4.;
         let bar_offset = (pos - track.start_offset) % bar_secs;
         let beat_in_bar = (bar_offset / bar_secs) * 4.;
+        // The track loops on a bar line.
+        if music_instance.last_beat_in_bar.map(|last| beat_in_bar < last).unwrap_or(false) {
+            music_instance.bars += 1;
+        }
+        music_instance.last_beat_in_bar = Some(beat_in_bar);
         let time = MusicTime {
             loop_position: pos,
+            beat: (pos - track.start_offset) / beat_secs,
             beat_in_bar: beat_in_bar,
+            song_beat: music_instance.bars as f64 * 4. + beat_in_bar,
+            beat_secs,
         };
         log::trace!("MusicTime: {:?}", time);
         music_time_writer.send(time);
@@ -92,6 +191,8 @@ fn on_update(
 
 fn stop_music(
     audio: Res<Audio>,
+    channels: Res<Channels>,
 ) {
     audio.stop();
+    audio.stop_channel(&channels.music);
 }
@@ -0,0 +1,153 @@
+//! Moving through a list of options on screens with more than a couple of them.
+//!
+//! With the one button a short press moves to the next option and a long press chooses it.
+//! The arrow keys and Enter work too, and clicking or tapping an option chooses it.
+
+use bevy::{
+    ecs::system::SystemParam,
+    input::touch::Touches,
+    prelude::*,
+};
+use crate::{
+    input::PrimaryButton,
+    layout::Layout,
+};
+use std::time::Duration;
+
+/// Holding the button at least this long chooses the selected option.
+const LONG_PRESS: Duration = Duration::from_millis(500);
+/// How far either side of the centre the mouse picks an option in a list.
+const LIST_HALF_WIDTH: f32 = 150.;
+
+#[derive(Default)]
+pub struct NavigationState {
+    /// When the button went down, while it's held and hasn't chosen yet.
+    press_started: Option<Duration>,
+    last_cursor: Option<Vec2>,
+}
+
+#[derive(SystemParam)]
+pub struct Navigation<'a> {
+    button: PrimaryButton<'a>,
+    kb: ResMut<'a, Input<KeyCode>>,
+    layout: Res<'a, Layout>,
+    mouse: Res<'a, Input<MouseButton>>,
+    time: Res<'a, Time>,
+    touches: Res<'a, Touches>,
+    windows: Res<'a, Windows>,
+    state: Local<'a, NavigationState>,
+}
+
+impl<'a> Navigation<'a> {
+    /// Moves `selected` through `count` options, and returns `true` when the selected option
+    /// is chosen. `option_at` returns the option at a point in the world, for the mouse and
+    /// touch screen.
+    pub fn navigate(
+        &mut self,
+        selected: &mut usize,
+        count: usize,
+        option_at: impl Fn(Vec2) -> Option<usize>,
+    ) -> bool {
+        let now = self.time.time_since_startup();
+        let pressed = self.button.take();
+
+        let cursor = self.cursor();
+        let hovered = cursor.and_then(|c| option_at(c));
+        if let Some(hovered) = hovered {
+            // Only follow the cursor when it moves, so it doesn't fight the one button.
+            if cursor != self.state.last_cursor {
+                *selected = hovered;
+            }
+        }
+        self.state.last_cursor = cursor;
+
+        let clicked = hovered.filter(|_| self.mouse.just_pressed(MouseButton::Left));
+        let tapped = self.touches.iter_just_pressed()
+            .find_map(|touch| self.touch_to_world(touch.position()))
+            .and_then(&option_at);
+        if pressed {
+            if let Some(option) = clicked.or(tapped) {
+                *selected = option;
+                self.state.press_started = None;
+                return true;
+            }
+        }
+
+        if self.kb.just_pressed(KeyCode::Up) {
+            *selected = (*selected + count - 1) % count;
+        }
+        if self.kb.just_pressed(KeyCode::Down) {
+            *selected = (*selected + 1) % count;
+        }
+        if self.kb.just_pressed(KeyCode::Return) {
+            // .reset() the key so the next state doesn't think it's already pressed
+            self.kb.reset(KeyCode::Return);
+            return true;
+        }
+
+        if pressed {
+            self.state.press_started = Some(now);
+        }
+        if let Some(started) = self.state.press_started {
+            if !self.button.held() {
+                self.state.press_started = None;
+                *selected = (*selected + 1) % count;
+            } else if now - started >= LONG_PRESS {
+                self.state.press_started = None;
+                return true;
+            }
+        }
+        false
+    }
+
+    /// -1 or 1 when Left or Right is pressed, to change the value of the selected option.
+    pub fn step(&self) -> i32 {
+        if self.kb.just_pressed(KeyCode::Left) {
+            -1
+        } else if self.kb.just_pressed(KeyCode::Right) {
+            1
+        } else {
+            0
+        }
+    }
+
+    /// Whether Escape was pressed to leave the screen.
+    pub fn back(&mut self) -> bool {
+        if self.kb.just_pressed(KeyCode::Escape) {
+            self.kb.reset(KeyCode::Escape);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// The cursor position in the world.
+    fn cursor(&self) -> Option<Vec2> {
+        let window = self.windows.get_primary()?;
+        let position = window.cursor_position()?;
+        Some(self.layout.window_to_world(window, position))
+    }
+
+    /// A touch position in the world. Bevy measures touches from the top of the window,
+    /// except on mobile where they're flipped to match the cursor.
+    fn touch_to_world(&self, position: Vec2) -> Option<Vec2> {
+        let window = self.windows.get_primary()?;
+        let position =
+            if cfg!(any(target_os = "android", target_os = "ios")) {
+                position
+            } else {
+                Vec2::new(position.x, window.height() - position.y)
+            };
+        Some(self.layout.window_to_world(window, position))
+    }
+}
+
+/// The option at `point` in a list centred horizontally, with the first option at `first_y`.
+pub fn option_in_list(point: Vec2, first_y: f32, spacing: f32, count: usize) -> Option<usize> {
+    let index = ((first_y - point.y) / spacing + 0.5).floor();
+    if point.x.abs() < LIST_HALF_WIDTH && index >= 0. && (index as usize) < count {
+        Some(index as usize)
+    } else {
+        None
+    }
+}
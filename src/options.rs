@@ -0,0 +1,373 @@
+//! The options screen, for the audio, display, gameplay and accessibility settings.
+//!
+//! Changes are made to the `Settings` resource, which applies and saves them as soon as they
+//! change. The music plays while the screen is open, with a marker pulsing on each beat, so
+//! the latency offset can be set until the two line up.
+
+use bevy::prelude::*;
+use crate::{
+    events::MusicTime,
+    game_state::GameState,
+    input::Rebinding,
+    layout::{Anchor, Anchored},
+    loading::Fonts,
+    navigation::{self, Navigation},
+    resources::Difficulty,
+    settings::{Scaling, Settings},
+};
+
+pub struct Plugin;
+
+struct OptionsEntity;
+
+#[derive(Clone, Copy, Debug)]
+enum Item {
+    MusicVolume,
+    SfxVolume,
+    LatencyOffset,
+    Fullscreen,
+    Scaling,
+    Difficulty,
+    ShowGhost,
+    ShowWeaknesses,
+    ReducedFlashing,
+    ColorblindIcons,
+    AutoDefend,
+    AutoAttack,
+    SlowTempo,
+    HoldToAct,
+    OneButton,
+    Back,
+}
+
+struct OptionText {
+    index: usize,
+}
+
+struct BeatMarker;
+
+struct Options {
+    selected: usize,
+}
+
+struct Sprites {
+    beat_marker: Handle<ColorMaterial>,
+}
+
+const ITEMS: [Item; 16] = [
+    Item::MusicVolume,
+    Item::SfxVolume,
+    Item::LatencyOffset,
+    Item::Fullscreen,
+    Item::Scaling,
+    Item::Difficulty,
+    Item::ShowGhost,
+    Item::ShowWeaknesses,
+    Item::ReducedFlashing,
+    Item::ColorblindIcons,
+    Item::AutoDefend,
+    Item::AutoAttack,
+    Item::SlowTempo,
+    Item::HoldToAct,
+    Item::OneButton,
+    Item::Back,
+];
+
+const OPTIONS_Y: f32 = 195.;
+const OPTION_SPACING: f32 = 26.;
+const VOLUME_STEP: f32 = 0.1;
+const LATENCY_STEP_MS: i32 = 10;
+const MAX_LATENCY_MS: i32 = 300;
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .insert_resource(Options { selected: 0 })
+            .add_system_set(
+                SystemSet::on_enter(GameState::CreateResources)
+                    .with_system(create_resources.system()))
+            .add_system_set(
+                SystemSet::on_enter(GameState::Options)
+                    .with_system(on_enter.system()))
+            .add_system_set(
+                SystemSet::on_update(GameState::Options)
+                    .with_system(navigate.system())
+                    .with_system(update_text.system())
+                    .with_system(pulse_beat_marker.system()))
+            .add_system_set(
+                SystemSet::on_exit(GameState::Options)
+                    .with_system(cleanup.system()))
+            ;
+    }
+}
+
+impl Item {
+    fn label(&self, settings: &Settings, rebinding: bool) -> String {
+        let on_off = |on: bool| if on { "On" } else { "Off" };
+        let a11y = &settings.accessibility;
+        match self {
+            Item::MusicVolume =>
+                format!("Music volume: {:.0}%", settings.audio.music_volume * 100.),
+            Item::SfxVolume =>
+                format!("Effects volume: {:.0}%", settings.audio.sfx_volume * 100.),
+            Item::LatencyOffset =>
+                format!("Latency offset: {} ms", settings.audio.latency_offset_ms),
+            Item::Fullscreen =>
+                format!("Window: {}",
+                        if settings.display.fullscreen { "Fullscreen" } else { "Windowed" }),
+            Item::Scaling => format!("Scaling: {:?}", settings.display.scaling),
+            Item::Difficulty => format!("Difficulty: {:?}", settings.gameplay.difficulty),
+            Item::ShowGhost => format!("Ghost: {}", on_off(settings.gameplay.show_ghost)),
+            Item::ShowWeaknesses =>
+                format!("Enemy weaknesses: {}", on_off(settings.gameplay.show_weaknesses)),
+            Item::ReducedFlashing => format!("Reduced flashing: {}", on_off(a11y.reduced_flashing)),
+            Item::ColorblindIcons => format!("Colorblind icons: {}", on_off(a11y.colorblind_icons)),
+            Item::AutoDefend => format!("Auto defend: {}", on_off(a11y.auto_defend)),
+            Item::AutoAttack => format!("Auto attack: {}", on_off(a11y.auto_attack)),
+            Item::SlowTempo => format!("Slow tempo: {}", on_off(a11y.slow_tempo)),
+            Item::HoldToAct => format!("Hold to act: {}", on_off(a11y.hold_to_act)),
+            Item::OneButton =>
+                if rebinding {
+                    "One button: press a key, mouse or gamepad button, tap to cancel".to_owned()
+                } else {
+                    format!("One button: {}", settings.keys.describe())
+                },
+            Item::Back => "Back".to_owned(),
+        }
+    }
+
+    /// Change the setting by `step`, -1 or 1. Volumes stop at 0% and 100%.
+    fn change(&self, settings: &mut Settings, step: i32) {
+        let a11y = &mut settings.accessibility;
+        match self {
+            Item::MusicVolume => step_volume(&mut settings.audio.music_volume, step),
+            Item::SfxVolume => step_volume(&mut settings.audio.sfx_volume, step),
+            Item::LatencyOffset => {
+                let range = MAX_LATENCY_MS * 2 + LATENCY_STEP_MS;
+                let ms = settings.audio.latency_offset_ms + step * LATENCY_STEP_MS;
+                settings.audio.latency_offset_ms =
+                    (ms + MAX_LATENCY_MS).rem_euclid(range) - MAX_LATENCY_MS;
+            },
+            Item::Fullscreen => settings.display.fullscreen = !settings.display.fullscreen,
+            Item::Scaling => {
+                settings.display.scaling = match settings.display.scaling {
+                    Scaling::Letterbox => Scaling::Fit,
+                    Scaling::Fit => Scaling::Letterbox,
+                };
+            },
+            Item::Difficulty => {
+                let difficulty = &mut settings.gameplay.difficulty;
+                *difficulty = match (*difficulty, step > 0) {
+                    (Difficulty::Easy, true) | (Difficulty::Hard, false) => Difficulty::Normal,
+                    (Difficulty::Normal, true) | (Difficulty::Easy, false) => Difficulty::Hard,
+                    (Difficulty::Hard, true) | (Difficulty::Normal, false) => Difficulty::Easy,
+                };
+            },
+            Item::ShowGhost => settings.gameplay.show_ghost = !settings.gameplay.show_ghost,
+            Item::ShowWeaknesses =>
+                settings.gameplay.show_weaknesses = !settings.gameplay.show_weaknesses,
+            Item::ReducedFlashing => a11y.reduced_flashing = !a11y.reduced_flashing,
+            Item::ColorblindIcons => a11y.colorblind_icons = !a11y.colorblind_icons,
+            Item::AutoDefend => a11y.auto_defend = !a11y.auto_defend,
+            Item::AutoAttack => a11y.auto_attack = !a11y.auto_attack,
+            Item::SlowTempo => a11y.slow_tempo = !a11y.slow_tempo,
+            Item::HoldToAct => a11y.hold_to_act = !a11y.hold_to_act,
+            Item::OneButton | Item::Back => {},
+        }
+    }
+
+    /// Choosing the option steps forwards and wraps around, so every value can be reached
+    /// with the one button.
+    fn choose(&self, settings: &mut Settings) {
+        let volume = match self {
+            Item::MusicVolume => &mut settings.audio.music_volume,
+            Item::SfxVolume => &mut settings.audio.sfx_volume,
+            _ => return self.change(settings, 1),
+        };
+        if *volume >= 1. - VOLUME_STEP / 2. {
+            *volume = 0.;
+        } else {
+            step_volume(volume, 1);
+        }
+    }
+}
+
+fn step_volume(volume: &mut f32, step: i32) {
+    let steps = (1. / VOLUME_STEP).round() as i32;
+    let current = (*volume / VOLUME_STEP).round() as i32;
+    *volume = (current + step).clamp(0, steps) as f32 * VOLUME_STEP;
+}
+
+fn create_resources(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands.insert_resource(Sprites {
+        beat_marker: materials.add(Color::rgb(242./255., 0., 48./255.).into()),
+    });
+}
+
+fn on_enter(
+    mut commands: Commands,
+    fonts: Res<Fonts>,
+    mut options: ResMut<Options>,
+    rebinding: Res<Rebinding>,
+    settings: Res<Settings>,
+    sprites: Res<Sprites>,
+) {
+    options.selected = 0;
+
+    let alignment = TextAlignment {
+        vertical: VerticalAlign::Center,
+        horizontal: HorizontalAlign::Center,
+    };
+
+    commands.spawn_bundle(Text2dBundle {
+        text: Text::with_section(
+            "Options",
+            TextStyle {
+                font: fonts.fiendish.clone(),
+                font_size: 60.,
+                color: Color::rgb(242./255., 0., 48./255.),
+            },
+            alignment),
+        transform: Transform {
+            translation: Vec3::new(0., 245., 10.),
+            .. Default::default()
+        },
+        .. Default::default()
+    }).insert(Anchored::new(Anchor::Top))
+      .insert(OptionsEntity);
+
+    for (index, item) in ITEMS.iter().enumerate() {
+        commands.spawn_bundle(Text2dBundle {
+            text: Text::with_section(
+                item.label(&settings, rebinding.0),
+                TextStyle {
+                    font: fonts.fira_sans.clone(),
+                    font_size: 22.,
+                    color: Color::WHITE,
+                },
+                alignment),
+            transform: Transform {
+                translation: Vec3::new(0., OPTIONS_Y - OPTION_SPACING * index as f32, 10.),
+                .. Default::default()
+            },
+            .. Default::default()
+        }).insert(OptionText { index })
+          .insert(OptionsEntity);
+    }
+
+    let latency_index = ITEMS.iter().position(|i| matches!(i, Item::LatencyOffset)).unwrap();
+    commands.spawn_bundle(SpriteBundle {
+        material: sprites.beat_marker.clone(),
+        sprite: Sprite::new(Vec2::new(16., 16.)),
+        transform: Transform {
+            translation: Vec3::new(190., OPTIONS_Y - OPTION_SPACING * latency_index as f32, 10.),
+            .. Default::default()
+        },
+        .. Default::default()
+    }).insert(BeatMarker)
+      .insert(OptionsEntity);
+
+    commands.spawn_bundle(Text2dBundle {
+        text: Text::with_section(
+            "Tap to move, hold to change. Arrow keys work too",
+            TextStyle {
+                font: fonts.fira_sans.clone(),
+                font_size: 20.,
+                color: Color::WHITE,
+            },
+            alignment),
+        transform: Transform {
+            translation: Vec3::new(0., -260., 10.),
+            .. Default::default()
+        },
+        .. Default::default()
+    }).insert(Anchored::new(Anchor::Bottom))
+      .insert(OptionsEntity);
+}
+
+fn navigate(
+    mut nav: Navigation,
+    mut options: ResMut<Options>,
+    mut rebinding: ResMut<Rebinding>,
+    mut settings: ResMut<Settings>,
+    mut state: ResMut<State<GameState>>,
+) {
+    // Every key goes to the binding until it's done.
+    if rebinding.0 {
+        return;
+    }
+
+    if nav.back() {
+        state.set(GameState::Menu).unwrap();
+        return;
+    }
+
+    let chosen = nav.navigate(&mut options.selected, ITEMS.len(), |point| {
+        navigation::option_in_list(point, OPTIONS_Y, OPTION_SPACING, ITEMS.len())
+    });
+    let item = ITEMS[options.selected];
+
+    let step = nav.step();
+    if step != 0 {
+        item.change(&mut settings, step);
+    }
+
+    if !chosen {
+        return;
+    }
+    match item {
+        Item::OneButton => rebinding.0 = true,
+        Item::Back => state.set(GameState::Menu).unwrap(),
+        _ => item.choose(&mut settings),
+    }
+}
+
+fn update_text(
+    mut query: Query<(&OptionText, &mut Text)>,
+    options: Res<Options>,
+    rebinding: Res<Rebinding>,
+    settings: Res<Settings>,
+) {
+    for (option, mut text) in query.iter_mut() {
+        let section = &mut text.sections[0];
+        if settings.is_changed() || rebinding.is_changed() {
+            section.value = ITEMS[option.index].label(&settings, rebinding.0);
+        }
+        section.style.color =
+            if option.index == options.selected {
+                Color::rgb(242./255., 0., 48./255.)
+            } else {
+                Color::WHITE
+            };
+    }
+}
+
+/// Grows the marker on each beat the player should hear, after the latency offset.
+fn pulse_beat_marker(
+    mut music_time_reader: EventReader<MusicTime>,
+    mut query: Query<&mut Transform, With<BeatMarker>>,
+) {
+    let music_time = match music_time_reader.iter().last() {
+        Some(t) => t,
+        None => return,
+    };
+    let since_beat = music_time.beat.rem_euclid(1.) as f32;
+    let scale = 1. + (1. - since_beat).powi(4);
+    for mut transform in query.iter_mut() {
+        transform.scale = Vec3::new(scale, scale, 1.);
+    }
+}
+
+fn cleanup(
+    mut commands: Commands,
+    query: Query<Entity, With<OptionsEntity>>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    for ent in query.iter() {
+        commands.entity(ent).despawn();
+    }
+    rebinding.0 = false;
+}
@@ -0,0 +1,237 @@
+//! Pauses a fight with Escape, or when the window loses focus.
+//!
+//! The music is paused, and on resuming every time-based component is moved on by the time
+//! spent paused, so nothing happens early. The countdown then starts again, timed to end on
+//! a bar so the fight picks up on the beat.
+
+use bevy::{
+    prelude::*,
+    window::WindowFocused,
+};
+use bevy_kira_audio::Audio;
+use crate::{
+    components::{AnimateSpriteSheet, DespawnAfter},
+    enemy::{AttackAnimation, RespawnTimer},
+    events::MusicTime,
+    fight_display::HideAfter,
+    floating_text::FloatingText,
+    game_state::GameState,
+    input::PrimaryButton,
+    layout::{FillScreen, VIRTUAL_HEIGHT, VIRTUAL_WIDTH},
+    loading::Fonts,
+    music::Channels,
+    player::AnimationState,
+    settings::Settings,
+    systems::countdown::{self, CountdownDelay},
+};
+use std::time::Duration;
+
+pub struct Plugin;
+
+#[derive(Default)]
+struct Pause {
+    started: Duration,
+    /// From the latest `MusicTime`, to line the countdown up with the music on resuming.
+    beat_in_bar: f64,
+    beat_secs: f64,
+}
+
+struct PauseEntity;
+
+struct Sprites {
+    overlay: Handle<ColorMaterial>,
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .insert_resource(Pause::default())
+            .add_system_set(
+                SystemSet::on_enter(GameState::CreateResources)
+                    .with_system(create_resources.system()))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(track_music.system())
+                    .with_system(pause_input.system()))
+            .add_system_set(
+                SystemSet::on_enter(GameState::Paused)
+                    .with_system(on_enter.system()))
+            .add_system_set(
+                SystemSet::on_update(GameState::Paused)
+                    .with_system(resume_input.system()))
+            .add_system_set(
+                SystemSet::on_exit(GameState::Paused)
+                    .with_system(on_exit.system())
+                    .with_system(cleanup.system()))
+            ;
+    }
+}
+
+fn create_resources(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands.insert_resource(Sprites {
+        overlay: materials.add(Color::rgba(0., 0., 0., 0.6).into()),
+    });
+}
+
+fn track_music(
+    mut music_time_reader: EventReader<MusicTime>,
+    mut pause: ResMut<Pause>,
+) {
+    if let Some(music_time) = music_time_reader.iter().last() {
+        pause.beat_in_bar = music_time.beat_in_bar;
+        pause.beat_secs = music_time.beat_secs;
+    }
+}
+
+fn pause_input(
+    mut focused_reader: EventReader<WindowFocused>,
+    mut kb: ResMut<Input<KeyCode>>,
+    mut state: ResMut<State<GameState>>,
+) {
+    let lost_focus = focused_reader.iter().any(|e| !e.focused);
+    if kb.just_pressed(KeyCode::Escape) || lost_focus {
+        // .reset() the key so Paused state doesn't think it's already pressed
+        kb.reset(KeyCode::Escape);
+        state.push(GameState::Paused).unwrap();
+    }
+}
+
+fn on_enter(
+    mut commands: Commands,
+    audio: Res<Audio>,
+    channels: Res<Channels>,
+    fonts: Res<Fonts>,
+    mut pause: ResMut<Pause>,
+    settings: Res<Settings>,
+    sprites: Res<Sprites>,
+    time: Res<Time>,
+) {
+    pause.started = time.time_since_startup();
+    audio.pause_channel(&channels.music);
+
+    commands.spawn_bundle(SpriteBundle {
+        material: sprites.overlay.clone(),
+        sprite: Sprite::new(Vec2::new(VIRTUAL_WIDTH, VIRTUAL_HEIGHT)),
+        transform: Transform {
+            translation: Vec3::new(0., 0., 20.),
+            .. Default::default()
+        },
+        .. Default::default()
+    }).insert(FillScreen)
+      .insert(PauseEntity);
+
+    let alignment = TextAlignment {
+        vertical: VerticalAlign::Center,
+        horizontal: HorizontalAlign::Center,
+    };
+
+    commands.spawn_bundle(Text2dBundle {
+        text: Text::with_section(
+            "Paused",
+            TextStyle {
+                font: fonts.fiendish.clone(),
+                font_size: 60.,
+                color: Color::rgb(242./255., 0., 48./255.),
+            },
+            alignment),
+        transform: Transform {
+            translation: Vec3::new(0., 40., 21.),
+            .. Default::default()
+        },
+        .. Default::default()
+    }).insert(PauseEntity);
+
+    commands.spawn_bundle(Text2dBundle {
+        text: Text::with_section(
+            settings.keys.prompt("resume"),
+            TextStyle {
+                font: fonts.fira_sans.clone(),
+                font_size: 20.,
+                color: Color::WHITE,
+            },
+            alignment),
+        transform: Transform {
+            translation: Vec3::new(0., -30., 21.),
+            .. Default::default()
+        },
+        .. Default::default()
+    }).insert(PauseEntity);
+}
+
+fn resume_input(
+    mut button: PrimaryButton,
+    mut kb: ResMut<Input<KeyCode>>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if button.take() || kb.just_pressed(KeyCode::Escape) {
+        kb.reset(KeyCode::Escape);
+        state.pop().unwrap();
+    }
+}
+
+/// Move every time-based component on by the time spent paused, and resume the music.
+fn on_exit(
+    mut despawn_query: Query<&mut DespawnAfter>,
+    mut animate_query: Query<&mut AnimateSpriteSheet>,
+    mut attack_query: Query<&mut AttackAnimation>,
+    mut respawn_query: Query<&mut RespawnTimer>,
+    mut player_anim_query: Query<&mut AnimationState>,
+    mut hide_query: Query<&mut HideAfter>,
+    mut floating_query: Query<&mut FloatingText>,
+    audio: Res<Audio>,
+    channels: Res<Channels>,
+    mut countdown_delay: ResMut<CountdownDelay>,
+    pause: Res<Pause>,
+    time: Res<Time>,
+) {
+    let paused_for = time.time_since_startup() - pause.started;
+
+    for mut despawn_after in despawn_query.iter_mut() {
+        despawn_after.after += paused_for;
+    }
+    for mut anim in animate_query.iter_mut() {
+        anim.next_frame_time += paused_for;
+    }
+    for mut attack in attack_query.iter_mut() {
+        attack.until += paused_for;
+    }
+    for mut respawn in respawn_query.iter_mut() {
+        respawn.at += paused_for;
+    }
+    for mut anim in player_anim_query.iter_mut() {
+        match &mut *anim {
+            AnimationState::Dead { until } | AnimationState::Attacking { until, .. } =>
+                *until += paused_for,
+            AnimationState::Idle => {},
+        }
+    }
+    for mut hide_after in hide_query.iter_mut() {
+        hide_after.when += paused_for;
+    }
+    for mut floating in floating_query.iter_mut() {
+        floating.spawned += paused_for;
+    }
+
+    audio.resume_channel(&channels.music);
+
+    // The countdown restarts when Playing resumes. The music carries on from where it was
+    // paused, so wait until the countdown will end on the start of a bar.
+    if pause.beat_secs > 0. {
+        let bar_secs = pause.beat_secs * 4.;
+        let pos_in_bar = pause.beat_in_bar * pause.beat_secs;
+        let wait = (-(pos_in_bar + countdown::LENGTH.as_secs_f64())).rem_euclid(bar_secs);
+        countdown_delay.0 = Duration::from_secs_f64(wait);
+    }
+}
+
+fn cleanup(
+    mut commands: Commands,
+    query: Query<Entity, With<PauseEntity>>,
+) {
+    for ent in query.iter() {
+        commands.entity(ent).despawn();
+    }
+}
@@ -1,21 +1,22 @@
 use bevy::prelude::*;
 use bevy_kira_audio::Audio;
 use crate::{
-    components::{AnimateSpriteSheet, DespawnAfter, Health, Player},
-    events::{DamageApplied, PlayerAttackAction, PlayerDefendAction},
+    components::{AnimateSpriteSheet, DespawnAfter, Flashing, Health, Player},
+    events::{DamageApplied, Healed, PlayerAttackAction, PlayerDefendAction, PlayerHealAction,
+             RunEnded},
     loading::Sounds,
+    resources::{Resumed, Upgrades},
     types::{DamageType, Hp},
     game_state::GameState,
+    health_bar::{HealthBar, HealthBarStyle},
     loading,
 };
 use std::time::Duration;
 
 pub struct Plugin;
 
-struct HpBackground;
-struct HpBar;
-
 const START_HP: Hp = 10;
+const HEAL_HP: Hp = 1;
 
 struct Sprites {
     idle: Handle<ColorMaterial>,
@@ -24,13 +25,14 @@ struct Sprites {
     attack_sword: Handle<ColorMaterial>,
     dead: Handle<ColorMaterial>,
     magic_ball: Handle<ColorMaterial>,
+    ray_ball: Handle<ColorMaterial>,
+    heal: Handle<ColorMaterial>,
     blood_splatter: Handle<TextureAtlas>,
-    health_background: Handle<ColorMaterial>,
-    health_bar: Handle<ColorMaterial>,
+    health_bar: HealthBarStyle,
     shield_flash: Handle<TextureAtlas>,
 }
 
-enum AnimationState {
+pub enum AnimationState {
     Dead {
         until: Duration,
     },
@@ -49,8 +51,7 @@ impl bevy::app::Plugin for Plugin {
                     .with_system(create_resources.system()))
             .add_system_set(
                 SystemSet::on_enter(GameState::Playing)
-                    .with_system(spawn_player_hp.system())
-                    .with_system(spawn_player.system()))
+                    .with_system(spawn_player.system().after("reset_run")))
             .add_system_set(
                 SystemSet::on_update(GameState::Playing)
                     .with_system(update_player_display.system()))
@@ -58,6 +59,7 @@ impl bevy::app::Plugin for Plugin {
                 SystemSet::on_update(GameState::Playing)
                     .with_system(player_attack_visuals.system())
                     .with_system(player_defend_visuals.system())
+                    .with_system(player_heal.system().label("player_heal"))
                     .with_system(player_damage_applied.system())
                     .with_system(die.system()))
             ;
@@ -77,14 +79,20 @@ fn create_resources(
         attack_magic: materials.add(texture_assets.player_attack_magic.clone().into()),
         dead: materials.add(texture_assets.player_dead.clone().into()),
         magic_ball: materials.add(texture_assets.icon_magic.clone().into()),
+        ray_ball: materials.add(ColorMaterial::modulated_texture(texture_assets.icon_magic.clone(),
+                                                                 Color::ORANGE_RED)),
+        heal: materials.add(ColorMaterial::modulated_texture(texture_assets.icon_magic.clone(),
+                                                             Color::LIME_GREEN)),
         blood_splatter: texture_atlases.add(
             TextureAtlas::from_grid(texture_assets.blood_splatter.clone(),
                                     Vec2::new(70., 51.),
                                     6, // columns
                                     1  // rows
                                     )),
-        health_background: materials.add(texture_assets.health_player.clone().into()),
-        health_bar: materials.add(Color::rgb(64./255., 1., 0.).into()),
+        health_bar: HealthBarStyle {
+            background: materials.add(texture_assets.health_player.clone().into()),
+            fill: materials.add(Color::rgb(64./255., 1., 0.).into()),
+        },
         shield_flash: texture_atlases.add(
             TextureAtlas::from_grid(texture_assets.shield_flash_sheet.clone(),
                                     Vec2::new(280., 420.),
@@ -97,16 +105,23 @@ fn create_resources(
 fn spawn_player(
     mut commands: Commands,
     player_query: Query<Entity, With<Player>>,
+    mut resumed: ResMut<Resumed>,
     sprites: Res<Sprites>,
+    upgrades: Res<Upgrades>,
 ) {
     for ent in player_query.iter() {
-        commands.entity(ent).despawn();
+        commands.entity(ent).despawn_recursive();
+    }
+
+    let mut health = player_start_health(&upgrades);
+    if let Some(hp) = resumed.player_hp.take() {
+        health.current = hp.min(health.max);
     }
 
     commands
         .spawn()
         .insert(Player)
-        .insert(player_start_health())
+        .insert(health)
         .insert(AnimationState::Idle)
         .insert_bundle(SpriteBundle {
             material: sprites.idle.clone(),
@@ -116,44 +131,17 @@ fn spawn_player(
                 .. Default::default()
             },
             .. Default::default()
+        })
+        .with_children(|parent| {
+            parent.spawn()
+                .insert(HealthBar::new(sprites.health_bar.clone(), Vec2::new(0., -85.)));
         });
 }
 
-fn spawn_player_hp(
-    mut commands: Commands,
-    hp_bg_query: Query<Entity, With<HpBackground>>,
-    hp_bar_query: Query<Entity, With<HpBar>>,
-    sprites: Res<Sprites>,
-) {
-    for ent in hp_bg_query.iter() {
-        commands.entity(ent).despawn();
-    }
-
-    for ent in hp_bar_query.iter() {
-        commands.entity(ent).despawn();
-    }
-
-    commands.spawn_bundle(SpriteBundle {
-        material: sprites.health_background.clone(),
-        transform: Transform {
-            translation: Vec3::new(163., -112., 3.),
-            .. Default::default()
-        },
-        .. Default::default()
-    }).insert(HpBackground);
-
-    commands.spawn_bundle(SpriteBundle {
-        material: sprites.health_bar.clone(),
-        sprite: Sprite::new(Vec2::new(1.0, 1.0)),
-        transform: health_bar_transform(&player_start_health()),
-        .. Default::default()
-    }).insert(HpBar);
-}
-
-fn player_start_health() -> Health {
+fn player_start_health(upgrades: &Upgrades) -> Health {
     Health {
-        current: START_HP,
-        max: START_HP,
+        current: START_HP + upgrades.max_hp,
+        max: START_HP + upgrades.max_hp,
         vulnerable_to: vec![DamageType::Arrow, DamageType::Magic, DamageType::Ray,
                             DamageType::Sword],
     }
@@ -173,16 +161,21 @@ fn player_attack_visuals(
                 damage_type: attack.damage_type.clone(),
             };
 
-            if attack.damage_type == DamageType::Magic {
+            let ball = match attack.damage_type {
+                DamageType::Magic => Some((sprites.magic_ball.clone(), 0.25)),
+                DamageType::Light => Some((sprites.ray_ball.clone(), 0.4)),
+                _ => None,
+            };
+            if let Some((material, scale)) = ball {
                 commands.spawn()
                     .insert(DespawnAfter {
                         after: time.time_since_startup() + Duration::from_millis(300),
                     })
                     .insert_bundle(SpriteBundle {
-                        material: sprites.magic_ball.clone(),
+                        material,
                         transform: Transform {
                             translation: Vec3::new(163., 23., 2.),
-                            scale: Vec3::ONE * 0.25,
+                            scale: Vec3::ONE * scale,
                             .. Default::default()
                         },
                         .. Default::default()
@@ -220,6 +213,34 @@ fn player_defend_visuals(
             max_index: atlases.get(sprites.shield_flash.clone())
                               .map(|a| a.len() - 1).unwrap_or(0) as u32,
             loop_: false,
+        }).insert(Flashing);
+    }
+}
+
+fn player_heal(
+    mut commands: Commands,
+    mut heal_reader: EventReader<PlayerHealAction>,
+    mut healed_writer: EventWriter<Healed>,
+    mut player: Query<(Entity, &mut Health), With<Player>>,
+    sprites: Res<Sprites>,
+    time: Res<Time>,
+) {
+    if let Some(_heal) = heal_reader.iter().next() {
+        for (entity, mut health) in player.single_mut() {
+            health.current = (health.current + HEAL_HP).min(health.max);
+            healed_writer.send(Healed { target: entity });
+        }
+
+        commands.spawn_bundle(SpriteBundle {
+            material: sprites.heal.clone(),
+            transform: Transform {
+                translation: Vec3::new(163., -27., 4.),
+                scale: Vec3::ONE * 0.3,
+                .. Default::default()
+            },
+            .. Default::default()
+        }).insert(DespawnAfter {
+            after: time.time_since_startup() + Duration::from_millis(300),
         });
     }
 }
@@ -249,19 +270,20 @@ fn die(
 
 /// Update visuals from AnimationState
 fn update_player_display(
-    mut player: Query<(&mut AnimationState, &Health, &mut Handle<ColorMaterial>), With<Player>>,
-    mut hp_display: Query<&mut Transform, With<HpBar>>,
+    mut player: Query<(&mut AnimationState, &mut Handle<ColorMaterial>), With<Player>>,
+    mut run_ended_writer: EventWriter<RunEnded>,
     mut state: ResMut<State<GameState>>,
     sprites: Res<Sprites>,
     time: Res<Time>,
 ) {
-    for (mut anim, health, mut mat) in player.single_mut() {
+    for (mut anim, mut mat) in player.single_mut() {
         match *anim {
             AnimationState::Dead {
                 until,
             } => {
                 *mat = sprites.dead.clone();
                 if time.time_since_startup() > until {
+                    run_ended_writer.send(RunEnded { won: false });
                     state.set(GameState::GameOver).unwrap();
                 }
             }
@@ -269,10 +291,10 @@ fn update_player_display(
                 until, damage_type: ref dt,
             } if time.time_since_startup() < until => {
                 *mat = match dt {
-                    DamageType::Arrow => sprites.attack_arrow.clone(),
+                    DamageType::Arrow | DamageType::Pierce => sprites.attack_arrow.clone(),
                     DamageType::Sword => sprites.attack_sword.clone(),
-                    DamageType::Magic => sprites.attack_magic.clone(),
-                    DamageType::Ray => unreachable!(),
+                    DamageType::Magic | DamageType::Ray | DamageType::Light =>
+                        sprites.attack_magic.clone(),
                 }
             },
             _ => {
@@ -280,10 +302,6 @@ fn update_player_display(
                 *anim = AnimationState::Idle;
             },
         }
-
-        for mut hp_transform in hp_display.single_mut() {
-            *hp_transform = health_bar_transform(&health);
-        }
     }
 }
 
@@ -323,19 +341,7 @@ fn player_damage_applied(
                 next_frame_time: time.time_since_startup() + Duration::from_millis(100),
                 max_index: 5,
                 loop_: false,
-            });
+            }).insert(Flashing);
         }
     }
 }
-
-fn health_bar_transform(health: &Health) -> Transform {
-    assert!(health.max >= 1);
-    let portion = (health.current as f32) / (health.max as f32);
-    let width_pixels = portion * 162.;
-
-    Transform {
-        translation: Vec3::new(163. - (162. / 2.) + width_pixels / 2., -112., 4.),
-        scale: Vec3::new(width_pixels, 16., 1.),
-        .. Default::default()
-    }
-}
@@ -0,0 +1,353 @@
+//! Records each run as its seed, loadout and button presses, so it can be played back
+//! through the normal systems.
+//!
+//! Presses and snapshots are timestamped by `MusicTime::song_beat`. Presses are played back
+//! with the same song beat, so they're timed exactly as recorded.
+//!
+//! Systems that keep records across runs go in `live_on_update` or `live_on_exit`, so watching
+//! a replay doesn't change them.
+
+use bevy::{
+    ecs::schedule::ShouldRun,
+    prelude::*,
+};
+use crate::{
+    campaign::{Campaign, Song},
+    components::{Health, Player},
+    events::{ButtonPressed, MusicTime, RunEnded},
+    game_state::GameState,
+    resources::{Difficulty, Loadout, Mode, RunRng, RunStart, Score},
+    save::Saves,
+    settings::{Accessibility, Settings},
+    types::Hp,
+};
+use serde::{Deserialize, Serialize};
+
+pub struct Plugin;
+
+const SAVE_KEY: &str = "last_replay";
+/// The highest scoring run recorded, used for the ghost.
+pub const BEST_SAVE_KEY: &str = "best_replay";
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Replay {
+    pub seed: u64,
+    pub song: Song,
+    pub difficulty: Difficulty,
+    #[serde(default)]
+    pub mode: Mode,
+    pub loadout: Loadout,
+    /// Settings when recorded, to help reproduce bug reports. Not applied on playback.
+    pub settings: Settings,
+    /// `MusicTime::song_beat` of each press.
+    pub presses: Vec<f64>,
+    /// `Shop::selected` for each press in the shop.
+    pub shop_picks: Vec<usize>,
+    /// Progress at the start of each bar.
+    #[serde(default)]
+    pub snapshots: Vec<Snapshot>,
+    #[serde(default)]
+    pub final_score: u32,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct Snapshot {
+    /// `MusicTime::song_beat` when taken.
+    pub song_beat: f64,
+    pub score: u32,
+    pub combo: u32,
+    pub hp: Hp,
+}
+
+pub enum ReplayState {
+    Idle,
+    Recording(Replay),
+    /// Feeding a recorded run into the game instead of reading input.
+    Playing {
+        replay: Replay,
+        next_press: usize,
+        next_shop_pick: usize,
+        /// Put back when the replay ends.
+        before: Choices,
+    },
+}
+
+/// The player's choices that a replay overwrites with its own.
+pub struct Choices {
+    difficulty: Difficulty,
+    mode: Mode,
+    loadout: Loadout,
+}
+
+/// Sent by the menu's Replay item.
+pub struct ReplayRequested;
+
+/// Music position during the current run.
+#[derive(Default)]
+pub struct Clock {
+    /// The latest `MusicTime::song_beat`.
+    pub song_beat: f64,
+}
+
+const PLAYING_UPDATE: &str = "playing_update";
+const PLAYING_EXIT: &str = "playing_exit";
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .insert_resource(ReplayState::Idle)
+            .insert_resource(Clock::default())
+            .add_event::<ReplayRequested>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Menu)
+                    .with_system(watch.system()))
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(start.system().after("set_level")))
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(
+                        State::on_update(GameState::Playing).label(PLAYING_UPDATE))
+                    .with_system(tick_clock.system().label("replay_clock"))
+                    .with_system(record_presses.system()
+                                 .after("replay_clock")
+                                 .after("button_input"))
+                    .with_system(record_snapshots.system().after("replay_clock"))
+                    .with_system(play_presses.system()
+                                 .label("button_input")
+                                 .after("replay_clock")))
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(State::on_exit(GameState::Playing).label(PLAYING_EXIT))
+                    .with_system(end.system().label("replay_end")))
+            ;
+    }
+}
+
+/// `SystemSet::on_update(GameState::Playing)`, except while a replay is playing.
+pub fn live_on_update() -> SystemSet {
+    SystemSet::new()
+        .with_run_criteria(RunCriteria::pipe(PLAYING_UPDATE, unless_replaying.system()))
+}
+
+/// `SystemSet::on_exit(GameState::Playing)`, except when a replay ends.
+pub fn live_on_exit() -> SystemSet {
+    SystemSet::new()
+        .with_run_criteria(RunCriteria::pipe(PLAYING_EXIT, unless_replaying.system()))
+}
+
+fn unless_replaying(
+    In(should_run): In<ShouldRun>,
+    replay_state: Res<ReplayState>,
+) -> ShouldRun {
+    if replay_state.is_playing() {
+        ShouldRun::No
+    } else {
+        should_run
+    }
+}
+
+impl ReplayState {
+    pub fn is_playing(&self) -> bool {
+        matches!(self, ReplayState::Playing { .. })
+    }
+
+    /// During playback, the next recorded shop pick.
+    pub fn next_shop_pick(&mut self) -> Option<usize> {
+        match self {
+            ReplayState::Playing { replay, next_shop_pick, .. } => {
+                let pick = replay.shop_picks.get(*next_shop_pick).copied();
+                *next_shop_pick += 1;
+                pick
+            },
+            _ => None,
+        }
+    }
+
+    /// The accessibility options in effect. During playback these are the ones the run was
+    /// recorded with, so it plays out the same.
+    pub fn accessibility<'a>(&'a self, settings: &'a Settings) -> &'a Accessibility {
+        match self {
+            ReplayState::Playing { replay, .. } => &replay.settings.accessibility,
+            _ => &settings.accessibility,
+        }
+    }
+
+    pub fn record_shop_pick(&mut self, selected: usize) {
+        if let ReplayState::Recording(replay) = self {
+            replay.shop_picks.push(selected);
+        }
+    }
+}
+
+pub fn can_replay(saves: &Saves) -> bool {
+    saves.load::<Replay>(SAVE_KEY).is_some()
+}
+
+/// Plays back the last run when it's chosen on the menu.
+fn watch(
+    mut replay_reader: EventReader<ReplayRequested>,
+    mut difficulty: ResMut<Difficulty>,
+    mut loadout: ResMut<Loadout>,
+    mut mode: ResMut<Mode>,
+    mut replay_state: ResMut<ReplayState>,
+    mut run_rng: ResMut<RunRng>,
+    mut run_start: ResMut<RunStart>,
+    saves: Res<Saves>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if replay_reader.iter().next().is_none() {
+        return;
+    }
+
+    let replay: Replay = match saves.load(SAVE_KEY) {
+        Some(r) => r,
+        None => {
+            log::info!("No replay to play");
+            return;
+        }
+    };
+
+    let before = Choices {
+        difficulty: *difficulty,
+        mode: *mode,
+        loadout: loadout.clone(),
+    };
+    *difficulty = replay.difficulty;
+    *mode = replay.mode;
+    *loadout = replay.loadout.clone();
+    run_rng.next_seed = Some(replay.seed);
+    *run_start = RunStart::New;
+    *replay_state = ReplayState::Playing {
+        replay,
+        next_press: 0,
+        next_shop_pick: 0,
+        before,
+    };
+    state.set(GameState::Playing).unwrap();
+}
+
+fn start(
+    campaign: Res<Campaign>,
+    mut clock: ResMut<Clock>,
+    difficulty: Res<Difficulty>,
+    loadout: Res<Loadout>,
+    mode: Res<Mode>,
+    mut replay_state: ResMut<ReplayState>,
+    run_rng: Res<RunRng>,
+    run_start: Res<RunStart>,
+    settings: Res<Settings>,
+) {
+    *clock = Clock::default();
+
+    match *run_start {
+        RunStart::New => {
+            if !replay_state.is_playing() {
+                *replay_state = ReplayState::Recording(Replay {
+                    seed: run_rng.seed,
+                    song: campaign.song(0, *mode),
+                    difficulty: *difficulty,
+                    mode: *mode,
+                    loadout: loadout.clone(),
+                    settings: settings.clone(),
+                    presses: Vec::new(),
+                    shop_picks: Vec::new(),
+                    snapshots: Vec::new(),
+                    final_score: 0,
+                });
+            }
+        },
+        // A replay covers a run up to its first game over or suspend.
+        RunStart::Continue | RunStart::Resume => *replay_state = ReplayState::Idle,
+    }
+}
+
+fn tick_clock(
+    mut music_time_reader: EventReader<MusicTime>,
+    mut clock: ResMut<Clock>,
+) {
+    if let Some(music_time) = music_time_reader.iter().last() {
+        clock.song_beat = music_time.song_beat;
+    }
+}
+
+fn record_presses(
+    mut button_reader: EventReader<ButtonPressed>,
+    mut replay_state: ResMut<ReplayState>,
+) {
+    for press in button_reader.iter() {
+        if let ReplayState::Recording(replay) = &mut *replay_state {
+            replay.presses.push(press.song_beat);
+        }
+    }
+}
+
+fn record_snapshots(
+    player: Query<&Health, With<Player>>,
+    clock: Res<Clock>,
+    mut replay_state: ResMut<ReplayState>,
+    score: Res<Score>,
+) {
+    if let ReplayState::Recording(replay) = &mut *replay_state {
+        let bar = |song_beat: f64| (song_beat / 4.).floor();
+        let new_bar = replay.snapshots.last()
+            .map(|s| bar(s.song_beat) != bar(clock.song_beat))
+            .unwrap_or(true);
+        if new_bar {
+            replay.snapshots.push(Snapshot {
+                song_beat: clock.song_beat,
+                score: score.points,
+                combo: score.combo,
+                hp: player.single().map(|h| h.current).unwrap_or(0),
+            });
+        }
+    }
+}
+
+/// Presses are fed on the first frame at or after their recorded time, carrying that time.
+fn play_presses(
+    mut button_writer: EventWriter<ButtonPressed>,
+    clock: Res<Clock>,
+    mut replay_state: ResMut<ReplayState>,
+) {
+    if let ReplayState::Playing { replay, next_press, .. } = &mut *replay_state {
+        while *next_press < replay.presses.len() &&
+            replay.presses[*next_press] <= clock.song_beat
+        {
+            let song_beat = replay.presses[*next_press];
+            debug!("play_presses: emit ButtonPressed at {}", song_beat);
+            button_writer.send(ButtonPressed { song_beat });
+            *next_press += 1;
+        }
+    }
+}
+
+/// A recording is only kept if the run ended, rather than being suspended or quit.
+fn end(
+    mut run_ended_reader: EventReader<RunEnded>,
+    mut difficulty: ResMut<Difficulty>,
+    mut loadout: ResMut<Loadout>,
+    mut mode: ResMut<Mode>,
+    mut replay_state: ResMut<ReplayState>,
+    saves: Res<Saves>,
+    score: Res<Score>,
+) {
+    let ended = run_ended_reader.iter().next().is_some();
+    match std::mem::replace(&mut *replay_state, ReplayState::Idle) {
+        ReplayState::Recording(mut replay) if ended => {
+            replay.final_score = score.points;
+            saves.save(SAVE_KEY, &replay);
+
+            let best: Option<Replay> = saves.load(BEST_SAVE_KEY);
+            if best.map(|b| replay.final_score > b.final_score).unwrap_or(true) {
+                saves.save(BEST_SAVE_KEY, &replay);
+            }
+        },
+        ReplayState::Playing { before, .. } => {
+            *difficulty = before.difficulty;
+            *mode = before.mode;
+            *loadout = before.loadout;
+        },
+        _ => {},
+    }
+}
@@ -1,6 +1,13 @@
 //! Shared resources
 
 use bevy::prelude::*;
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use crate::{
+    campaign::Song,
+    components::{Action, Character},
+    types::{DamageType, Hp},
+};
 
 pub struct Icons {
     pub attack: Handle<ColorMaterial>,
@@ -13,9 +20,152 @@ pub enum Countdown {
     Counting,
 }
 
-#[derive(Eq, PartialEq)]
-pub enum Level {
-    /// 1 indexed mob level
-    Mob(u8),
-    Boss
+/// Position in the campaign.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Level {
+    /// 0 indexed into `Campaign::stages`.
+    pub stage: usize,
+    /// 0 indexed fight within the stage.
+    pub fight: usize,
+}
+
+/// How the run starts when entering GameState::Playing.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RunStart {
+    New,
+    /// Continue from `Checkpoint::stage`, keeping upgrades and coins.
+    Continue,
+    /// Resume a suspended run, everything was restored before entering Playing.
+    Resume,
+}
+
+/// The last checkpoint stage reached in this run.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Checkpoint {
+    /// 0 indexed into `Campaign::stages`.
+    pub stage: usize,
+    pub continues_left: u8,
+}
+
+impl Checkpoint {
+    pub fn can_continue(&self) -> bool {
+        self.stage > 0 && self.continues_left > 0
+    }
+}
+
+/// Which action sits in each slot of the action spinner.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Loadout {
+    /// Slots in order top, left, bottom, right.
+    pub slots: [Action; 4],
+}
+
+impl Default for Loadout {
+    fn default() -> Loadout {
+        Loadout {
+            slots: [Action::AttackSword, Action::AttackMagic, Action::Defend,
+                    Action::AttackArrow],
+        }
+    }
+}
+
+/// Coins earned by defeating enemies, spent in the shop between levels.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct Coins(pub u32);
+
+/// Upgrades bought in the shop. They last until the end of the run.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Upgrades {
+    /// Max HP added on top of the player's starting HP.
+    pub max_hp: Hp,
+    /// One entry per wider hit window bought, so may contain duplicates.
+    pub wider_windows: Vec<Action>,
+    /// One entry per extra damage upgrade bought, so may contain duplicates.
+    pub extra_damage: Vec<DamageType>,
+}
+
+impl Upgrades {
+    pub fn wider_window_count(&self, action: &Action) -> usize {
+        self.wider_windows.iter().filter(|a| *a == action).count()
+    }
+
+    pub fn damage_bonus(&self, damage_type: &DamageType) -> Hp {
+        self.extra_damage.iter().filter(|dt| *dt == damage_type).count() as Hp
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    /// Multiplier for the width of the action spinner's hit windows.
+    pub fn hit_window_scale(&self) -> f64 {
+        match self {
+            Difficulty::Easy => 1.5,
+            Difficulty::Normal => 1.,
+            Difficulty::Hard => 0.6,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Mode {
+    Campaign,
+    /// The player can't die, and the chosen song plays throughout.
+    Practice(Song),
+}
+
+impl Default for Mode {
+    fn default() -> Mode {
+        Mode::Campaign
+    }
+}
+
+/// Score for the current run.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Score {
+    pub points: u32,
+    /// Hits on enemies in a row without a blocked attack or taking damage.
+    pub combo: u32,
+    pub max_combo: u32,
+    pub hits: u32,
+    pub damage_taken: Hp,
+    pub continues_used: u8,
+}
+
+/// All randomness in a run comes from here, so a replay of the run can use the same seed.
+///
+/// Each use takes a new RNG seeded from the run's seed and the number of RNGs taken so far,
+/// so a suspended run can carry on the same sequence from just those two numbers.
+#[derive(Default)]
+pub struct RunRng {
+    pub seed: u64,
+    pub draws: u64,
+    /// Used by the next new run instead of a random seed.
+    pub next_seed: Option<u64>,
+}
+
+impl RunRng {
+    pub fn start_run(&mut self) {
+        self.seed = self.next_seed.take().unwrap_or_else(rand::random);
+        self.draws = 0;
+    }
+
+    pub fn rng(&mut self) -> StdRng {
+        let rng = StdRng::seed_from_u64(self.seed.wrapping_add(self.draws));
+        self.draws += 1;
+        rng
+    }
+}
+
+/// Health to restore when a suspended run's entities are spawned. Taken by the spawning
+/// systems, so it only applies once.
+#[derive(Default)]
+pub struct Resumed {
+    pub player_hp: Option<Hp>,
+    pub enemy: Option<(Character, Hp)>,
 }
@@ -0,0 +1,196 @@
+//! Persistent storage. Saves are JSON strings in files under the XDG data directory on
+//! native builds, and in `localStorage` on the web build.
+
+use serde::{de::DeserializeOwned, Serialize};
+
+pub trait Storage: Send + Sync {
+    fn load(&self, key: &str) -> Option<String>;
+    fn save(&self, key: &str, value: &str) -> anyhow::Result<()>;
+    fn remove(&self, key: &str) -> anyhow::Result<()>;
+}
+
+pub struct Saves {
+    storage: Box<dyn Storage>,
+}
+
+impl Saves {
+    /// Storage for the current platform. Inserted as a resource in `main`, after the
+    /// settings have been loaded from it.
+    pub fn new() -> Saves {
+        #[cfg(not(target_arch = "wasm32"))]
+        let storage = native::FileStorage::new();
+        #[cfg(target_arch = "wasm32")]
+        let storage = web::LocalStorage;
+
+        Saves {
+            storage: Box::new(storage),
+        }
+    }
+
+    /// Returns `None` if there is no save for `key` or it couldn't be parsed.
+    pub fn load<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let json = self.storage.load(key)?;
+        match serde_json::from_str(&json) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                log::warn!("Couldn't parse save '{}': {}", key, e);
+                None
+            }
+        }
+    }
+
+    /// Errors are logged rather than returned, there's not much else to do with them.
+    pub fn save<T: Serialize>(&self, key: &str, value: &T) {
+        let res = serde_json::to_string(value)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| self.storage.save(key, &json));
+        if let Err(e) = res {
+            log::error!("Couldn't write save '{}': {}", key, e);
+        }
+    }
+
+    pub fn remove(&self, key: &str) {
+        if let Err(e) = self.storage.remove(key) {
+            log::error!("Couldn't remove save '{}': {}", key, e);
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use std::path::PathBuf;
+
+    pub struct FileStorage {
+        dir: PathBuf,
+    }
+
+    impl FileStorage {
+        pub fn new() -> FileStorage {
+            FileStorage {
+                dir: dirs::data_dir()
+                    .unwrap_or_else(|| PathBuf::from("."))
+                    .join("one-click-ninja"),
+            }
+        }
+
+        fn path(&self, key: &str) -> PathBuf {
+            self.dir.join(format!("{}.json", key))
+        }
+    }
+
+    impl super::Storage for FileStorage {
+        fn load(&self, key: &str) -> Option<String> {
+            std::fs::read_to_string(self.path(key)).ok()
+        }
+
+        fn save(&self, key: &str, value: &str) -> anyhow::Result<()> {
+            std::fs::create_dir_all(&self.dir)?;
+            // Write then rename, so a crash mid-write doesn't lose the old save.
+            let tmp_path = self.path(&format!("{}.tmp", key));
+            std::fs::write(&tmp_path, value)?;
+            std::fs::rename(&tmp_path, self.path(key))?;
+            Ok(())
+        }
+
+        fn remove(&self, key: &str) -> anyhow::Result<()> {
+            match std::fs::remove_file(self.path(key)) {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                res => Ok(res?),
+            }
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod web {
+    pub struct LocalStorage;
+
+    fn local_storage() -> Option<web_sys::Storage> {
+        web_sys::window()?.local_storage().ok()?
+    }
+
+    fn item_key(key: &str) -> String {
+        format!("one-click-ninja.{}", key)
+    }
+
+    impl super::Storage for LocalStorage {
+        fn load(&self, key: &str) -> Option<String> {
+            local_storage()?.get_item(&item_key(key)).ok()?
+        }
+
+        fn save(&self, key: &str, value: &str) -> anyhow::Result<()> {
+            local_storage()
+                .ok_or_else(|| anyhow::anyhow!("localStorage is unavailable"))?
+                .set_item(&item_key(key), value)
+                .map_err(|e| anyhow::anyhow!("localStorage.setItem failed: {:?}", e))
+        }
+
+        fn remove(&self, key: &str) -> anyhow::Result<()> {
+            local_storage()
+                .ok_or_else(|| anyhow::anyhow!("localStorage is unavailable"))?
+                .remove_item(&item_key(key))
+                .map_err(|e| anyhow::anyhow!("localStorage.removeItem failed: {:?}", e))
+        }
+    }
+}
+
+/// Seconds since the Unix epoch. `SystemTime::now()` panics on wasm, so use `Date` there.
+pub fn now_unix_secs() -> u64 {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        (js_sys::Date::now() / 1000.) as u64
+    }
+}
+
+/// Format as a UTC date, e.g. "2021-08-31".
+pub fn format_date(unix_secs: u64) -> String {
+    // Howard Hinnant's civil_from_days algorithm.
+    let days = (unix_secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524
+                       - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_date;
+
+    #[test]
+    fn format_date_epoch() {
+        assert_eq!(format_date(0), "1970-01-01");
+    }
+
+    #[test]
+    fn format_date_end_of_day() {
+        assert_eq!(format_date(86399), "1970-01-01");
+        assert_eq!(format_date(86400), "1970-01-02");
+    }
+
+    #[test]
+    fn format_date_leap_days() {
+        assert_eq!(format_date(951782400), "2000-02-29");
+        assert_eq!(format_date(1582934400), "2020-02-29");
+        assert_eq!(format_date(1583020800), "2020-03-01");
+    }
+
+    #[test]
+    fn format_date_year_end() {
+        assert_eq!(format_date(1630368000), "2021-08-31");
+        assert_eq!(format_date(1640995199), "2021-12-31");
+        assert_eq!(format_date(1640995200), "2022-01-01");
+    }
+}
@@ -0,0 +1,91 @@
+use bevy::prelude::*;
+use crate::{
+    components::{Enemy, Player},
+    events::{DamageApplied, DamageBlocked, Die},
+    game_state::GameState,
+    resources::{RunStart, Score},
+};
+
+/// Keeps the `Score` resource up to date during a run.
+pub struct Plugin;
+
+const HIT_POINTS: u32 = 10;
+const KILL_POINTS: u32 = 50;
+/// Every this many hits in a combo adds another multiple of `HIT_POINTS`.
+const COMBO_STEP: u32 = 5;
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .insert_resource(Score::default())
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(reset_score.system().label("reset_score")))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .label("update_score")
+                    .with_system(damage_applied.system())
+                    .with_system(damage_blocked.system())
+                    .with_system(die.system()));
+    }
+}
+
+/// A continued run keeps its score, but counts the continue. A resumed run's score was
+/// restored with the rest of the run.
+fn reset_score(
+    run_start: Res<RunStart>,
+    mut score: ResMut<Score>,
+) {
+    match *run_start {
+        RunStart::New => *score = Score::default(),
+        RunStart::Continue => {
+            score.combo = 0;
+            score.continues_used += 1;
+        },
+        RunStart::Resume => score.combo = 0,
+    }
+}
+
+fn damage_applied(
+    mut damage_applied_reader: EventReader<DamageApplied>,
+    enemy_query: Query<&Enemy>,
+    player_query: Query<&Player>,
+    mut score: ResMut<Score>,
+) {
+    for damage_applied in damage_applied_reader.iter() {
+        let target = damage_applied.damage.target;
+        if enemy_query.get(target).is_ok() {
+            score.points += HIT_POINTS * (1 + score.combo / COMBO_STEP);
+            score.combo += 1;
+            score.max_combo = score.max_combo.max(score.combo);
+            score.hits += 1;
+        } else if player_query.get(target).is_ok() {
+            score.combo = 0;
+            score.damage_taken += damage_applied.damage.hp;
+        }
+    }
+}
+
+fn damage_blocked(
+    mut damage_blocked_reader: EventReader<DamageBlocked>,
+    enemy_query: Query<&Enemy>,
+    mut score: ResMut<Score>,
+) {
+    for blocked in damage_blocked_reader.iter() {
+        if enemy_query.get(blocked.damage.target).is_ok() {
+            score.combo = 0;
+        }
+    }
+}
+
+fn die(
+    mut die_reader: EventReader<Die>,
+    enemy_query: Query<&Enemy>,
+    mut score: ResMut<Score>,
+) {
+    for die in die_reader.iter() {
+        if enemy_query.get(die.target).is_ok() {
+            score.points += KILL_POINTS;
+        }
+    }
+}
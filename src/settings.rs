@@ -0,0 +1,285 @@
+//! Player settings, loaded from the save storage at startup and saved whenever they change.
+
+use bevy::{
+    prelude::*,
+    window::WindowMode,
+};
+use bevy_kira_audio::Audio;
+use crate::{
+    music::Channels,
+    resources::Difficulty,
+    save::Saves,
+};
+use serde::{Deserialize, Serialize};
+
+/// Applies changes to the `Settings` resource and saves them.
+///
+/// `Settings` itself is inserted in `main`, because the window size is needed before the
+/// app is built.
+pub struct Plugin;
+
+const SAVE_KEY: &str = "settings";
+
+/// Missing fields take their default value, so older settings files still load.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    pub audio: AudioSettings,
+    pub keys: KeyBindings,
+    pub display: DisplaySettings,
+    pub gameplay: GameplaySettings,
+    pub accessibility: Accessibility,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    /// 0 is silent, 1 is full volume.
+    pub music_volume: f32,
+    /// 0 is silent, 1 is full volume.
+    pub sfx_volume: f32,
+    /// How long sound takes to reach the player's ears, subtracted from the music position
+    /// so the beat lines up with what they hear.
+    pub latency_offset_ms: i32,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct KeyBindings {
+    /// The one button.
+    pub action: KeyCode,
+    /// Save the run and return to the menu.
+    pub suspend: KeyCode,
+    /// The one button on a gamepad. `None` accepts any button.
+    pub gamepad_button: Option<GamepadButtonType>,
+    /// The one button on a mouse. `None` ignores the mouse.
+    pub mouse_button: Option<MouseButton>,
+    /// Whether touching the screen presses the one button.
+    pub touch: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct DisplaySettings {
+    pub width: f32,
+    pub height: f32,
+    pub fullscreen: bool,
+    pub vsync: bool,
+    /// How the game fits a window that isn't 4:3.
+    pub scaling: Scaling,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Scaling {
+    /// Black bars cover the window outside the game.
+    Letterbox,
+    /// Show more of the scene, with the HUD at the edges of the window.
+    Fit,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct GameplaySettings {
+    /// Compare the current run against the best recorded run.
+    pub show_ghost: bool,
+    /// List what the current enemy is vulnerable to under its health bar.
+    pub show_weaknesses: bool,
+    /// Difficulty of new runs. Replays and resumed runs keep their own.
+    pub difficulty: Difficulty,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Accessibility {
+    /// Tone down flashing sprites and screen effects.
+    pub reduced_flashing: bool,
+    /// Mark action icons with shapes as well as colours.
+    pub colorblind_icons: bool,
+    /// Enemy attacks are always defended.
+    pub auto_defend: bool,
+    /// An attack pressed on time uses whichever attack in the loadout the enemy is vulnerable
+    /// to, so only the timing matters.
+    pub auto_attack: bool,
+    /// The spinner turns once every two bars instead of every bar. The music is unchanged.
+    pub slow_tempo: bool,
+    /// Hold the button and the action is taken when the pointer next reaches an icon,
+    /// instead of tapping on time.
+    pub hold_to_act: bool,
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_system(apply_audio.system())
+            .add_system(apply_display.system())
+            .add_system(save_settings.system());
+    }
+}
+
+impl Default for AudioSettings {
+    fn default() -> AudioSettings {
+        AudioSettings {
+            music_volume: 1.,
+            sfx_volume: 1.,
+            latency_offset_ms: 0,
+        }
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> KeyBindings {
+        KeyBindings {
+            action: KeyCode::Space,
+            suspend: KeyCode::Q,
+            gamepad_button: None,
+            mouse_button: Some(MouseButton::Left),
+            touch: true,
+        }
+    }
+}
+
+impl KeyBindings {
+    /// Every way the one button is bound, e.g. "Space, click or tap".
+    pub fn describe(&self) -> String {
+        let mut ways = vec![format!("{:?}", self.action)];
+        match self.mouse_button {
+            Some(MouseButton::Left) => ways.push("click".to_owned()),
+            Some(MouseButton::Right) => ways.push("right click".to_owned()),
+            Some(MouseButton::Middle) => ways.push("middle click".to_owned()),
+            Some(MouseButton::Other(n)) => ways.push(format!("mouse button {}", n)),
+            None => {},
+        }
+        if let Some(button) = self.gamepad_button {
+            ways.push(format!("gamepad {:?}", button));
+        }
+        if self.touch {
+            ways.push("tap".to_owned());
+        }
+        let last = ways.pop().unwrap();
+        if ways.is_empty() {
+            last
+        } else {
+            format!("{} or {}", ways.join(", "), last)
+        }
+    }
+
+    /// An instruction to press the one button, e.g. "Press Space, click or tap to return".
+    pub fn prompt(&self, to: &str) -> String {
+        format!("Press {} to {}", self.describe(), to)
+    }
+}
+
+impl Default for DisplaySettings {
+    fn default() -> DisplaySettings {
+        DisplaySettings {
+            width: 800.,
+            height: 600.,
+            fullscreen: false,
+            vsync: true, //Doesn't actually work (at least on linux)
+            scaling: Scaling::Letterbox,
+        }
+    }
+}
+
+impl Default for GameplaySettings {
+    fn default() -> GameplaySettings {
+        GameplaySettings {
+            show_ghost: true,
+            show_weaknesses: true,
+            difficulty: Difficulty::Normal,
+        }
+    }
+}
+
+impl Settings {
+    pub fn load(saves: &Saves) -> Settings {
+        saves.load(SAVE_KEY).unwrap_or_default()
+    }
+
+    pub fn window_descriptor(&self) -> WindowDescriptor {
+        WindowDescriptor {
+            title: "One-Click Ninja".to_string(),
+            width: self.display.width,
+            height: self.display.height,
+            vsync: self.display.vsync,
+            mode: self.display.window_mode(),
+            .. Default::default()
+        }
+    }
+}
+
+impl AudioSettings {
+    pub fn latency_offset_secs(&self) -> f64 {
+        self.latency_offset_ms as f64 / 1000.
+    }
+}
+
+impl Accessibility {
+    pub fn beats_per_turn(&self) -> f64 {
+        if self.slow_tempo {
+            8.
+        } else {
+            4.
+        }
+    }
+}
+
+impl DisplaySettings {
+    fn window_mode(&self) -> WindowMode {
+        if self.fullscreen {
+            WindowMode::BorderlessFullscreen
+        } else {
+            WindowMode::Windowed
+        }
+    }
+}
+
+fn apply_audio(
+    audio: Res<Audio>,
+    channels: Res<Channels>,
+    settings: Res<Settings>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    audio.set_volume_in_channel(settings.audio.music_volume, &channels.music);
+    audio.set_volume(settings.audio.sfx_volume);
+}
+
+fn apply_display(
+    mut applied: Local<Option<DisplaySettings>>,
+    settings: Res<Settings>,
+    mut windows: ResMut<Windows>,
+) {
+    // The window was created from these settings, only changes since need applying.
+    // Other settings changing mustn't undo the player resizing the window.
+    let display = &settings.display;
+    let previous = match applied.replace(display.clone()) {
+        Some(previous) if previous != *display => previous,
+        _ => return,
+    };
+
+    if let Some(window) = windows.get_primary_mut() {
+        if (previous.width, previous.height) != (display.width, display.height) {
+            window.set_resolution(display.width, display.height);
+        }
+        if previous.vsync != display.vsync {
+            window.set_vsync(display.vsync);
+        }
+        if previous.fullscreen != display.fullscreen {
+            window.set_mode(display.window_mode());
+        }
+    }
+}
+
+fn save_settings(
+    saves: Res<Saves>,
+    settings: Res<Settings>,
+) {
+    if !settings.is_changed() || settings.is_added() {
+        return;
+    }
+
+    saves.save(SAVE_KEY, &*settings);
+}
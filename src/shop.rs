@@ -0,0 +1,366 @@
+use bevy::prelude::*;
+use bevy_kira_audio::Audio;
+use crate::{
+    components::{Action, Health, Player},
+    events::Healed,
+    game_state::GameState,
+    input::PrimaryButton,
+    layout::{FillScreen, VIRTUAL_HEIGHT, VIRTUAL_WIDTH},
+    loading::{Fonts, Sounds},
+    music::Channels,
+    replay::ReplayState,
+    resources::{Coins, Loadout, RunRng, RunStart, Upgrades},
+    types::{DamageType, Hp},
+};
+use rand::seq::SliceRandom;
+use std::time::Duration;
+
+/// Offers upgrades between levels. The highlighted option cycles by itself, so the
+/// player only needs the one button to buy or continue. The music is paused until the
+/// fight carries on.
+pub struct Plugin;
+
+struct ShopEntity;
+
+struct ShopOption {
+    /// Index into `Shop::offers`, or `Shop::offers.len()` for "Continue".
+    index: usize,
+}
+
+struct CoinsText;
+
+struct Sprites {
+    overlay: Handle<ColorMaterial>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Upgrade {
+    MaxHp(Hp),
+    Heal(Hp),
+    WiderWindow(Action),
+    ExtraDamage(DamageType),
+}
+
+struct Shop {
+    /// `None` once an offer is sold.
+    offers: Vec<Option<Upgrade>>,
+    selected: usize,
+    next_cycle: Duration,
+}
+
+const NUM_OFFERS: usize = 3;
+const CYCLE_PERIOD: Duration = Duration::from_millis(900);
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_system_set(
+                SystemSet::on_enter(GameState::CreateResources)
+                    .with_system(create_resources.system()))
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(reset_run.system().label("reset_run")))
+            .add_system_set(
+                SystemSet::on_enter(GameState::Shop)
+                    .with_system(on_enter.system()))
+            .add_system_set(
+                SystemSet::on_update(GameState::Shop)
+                    .with_system(cycle_selection.system())
+                    .with_system(keyboard_input.system())
+                    .with_system(update_text.system()))
+            .add_system_set(
+                SystemSet::on_exit(GameState::Shop)
+                    .with_system(cleanup.system())
+                    .with_system(resume_music.system()))
+            ;
+    }
+}
+
+impl Upgrade {
+    fn cost(&self) -> u32 {
+        match self {
+            Upgrade::MaxHp(_) => 4,
+            Upgrade::Heal(_) => 2,
+            Upgrade::WiderWindow(_) => 3,
+            Upgrade::ExtraDamage(_) => 5,
+        }
+    }
+
+    fn description(&self) -> String {
+        match self {
+            Upgrade::MaxHp(hp) => format!("+{} max HP", hp),
+            Upgrade::Heal(hp) => format!("Heal {} HP", hp),
+            Upgrade::WiderWindow(action) => format!("Wider {} window", action.name()),
+            Upgrade::ExtraDamage(dt) => format!("+1 {:?} damage", dt),
+        }
+    }
+
+    fn apply(&self, upgrades: &mut Upgrades, health: &mut Health) {
+        match self {
+            Upgrade::MaxHp(hp) => {
+                upgrades.max_hp += hp;
+                health.max += hp;
+                health.current += hp;
+            },
+            Upgrade::Heal(hp) => {
+                health.current = (health.current + hp).min(health.max);
+            },
+            Upgrade::WiderWindow(action) => upgrades.wider_windows.push(action.clone()),
+            Upgrade::ExtraDamage(dt) => upgrades.extra_damage.push(dt.clone()),
+        }
+    }
+}
+
+/// Upgrades that are useful with the current loadout and health.
+fn all_upgrades(loadout: &Loadout, health: Option<&Health>) -> Vec<Upgrade> {
+    let mut upgrades = vec![Upgrade::MaxHp(2)];
+    // Buying max HP heals by as much, so a player at full health stays there.
+    if health.map(|h| h.current < h.max).unwrap_or(false) {
+        upgrades.push(Upgrade::Heal(3));
+    }
+    for action in loadout.slots.iter() {
+        let window = Upgrade::WiderWindow(action.clone());
+        if !upgrades.contains(&window) {
+            upgrades.push(window);
+        }
+        if let Some(dt) = action.damage_type() {
+            let damage = Upgrade::ExtraDamage(dt);
+            if !upgrades.contains(&damage) {
+                upgrades.push(damage);
+            }
+        }
+    }
+    upgrades
+}
+
+fn create_resources(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands.insert_resource(Coins(0));
+    commands.insert_resource(Upgrades::default());
+    commands.insert_resource(Shop {
+        offers: Vec::new(),
+        selected: 0,
+        next_cycle: Duration::default(),
+    });
+    commands.insert_resource(Sprites {
+        overlay: materials.add(Color::rgba(0., 0., 0., 0.8).into()),
+    });
+}
+
+/// Upgrades only last for one run, but are kept when continuing from a checkpoint.
+fn reset_run(
+    mut coins: ResMut<Coins>,
+    run_start: Res<RunStart>,
+    mut upgrades: ResMut<Upgrades>,
+) {
+    if *run_start == RunStart::New {
+        *coins = Coins(0);
+        *upgrades = Upgrades::default();
+    }
+}
+
+fn on_enter(
+    mut commands: Commands,
+    player: Query<&Health, With<Player>>,
+    audio: Res<Audio>,
+    channels: Res<Channels>,
+    fonts: Res<Fonts>,
+    loadout: Res<Loadout>,
+    mut run_rng: ResMut<RunRng>,
+    mut shop: ResMut<Shop>,
+    sprites: Res<Sprites>,
+    time: Res<Time>,
+) {
+    audio.pause_channel(&channels.music);
+
+    let mut offers = all_upgrades(&loadout, player.single().ok());
+    offers.shuffle(&mut run_rng.rng());
+    offers.truncate(NUM_OFFERS);
+    *shop = Shop {
+        offers: offers.into_iter().map(Some).collect(),
+        selected: 0,
+        next_cycle: time.time_since_startup() + CYCLE_PERIOD,
+    };
+
+    commands.spawn_bundle(SpriteBundle {
+        material: sprites.overlay.clone(),
+        sprite: Sprite::new(Vec2::new(VIRTUAL_WIDTH, VIRTUAL_HEIGHT)),
+        transform: Transform {
+            translation: Vec3::new(0., 0., 20.),
+            .. Default::default()
+        },
+        .. Default::default()
+    }).insert(FillScreen)
+      .insert(ShopEntity);
+
+    let text_style = |font: &Handle<Font>, font_size: f32| TextStyle {
+        font: font.clone(),
+        font_size,
+        color: Color::WHITE,
+    };
+    let alignment = TextAlignment {
+        vertical: VerticalAlign::Center,
+        horizontal: HorizontalAlign::Center,
+    };
+
+    commands.spawn_bundle(Text2dBundle {
+        text: Text::with_section("Shop", text_style(&fonts.fiendish, 60.), alignment),
+        transform: Transform {
+            translation: Vec3::new(0., 200., 21.),
+            .. Default::default()
+        },
+        .. Default::default()
+    }).insert(ShopEntity);
+
+    commands.spawn_bundle(Text2dBundle {
+        text: Text::with_section("", text_style(&fonts.fira_sans, 30.), alignment),
+        transform: Transform {
+            translation: Vec3::new(0., 140., 21.),
+            .. Default::default()
+        },
+        .. Default::default()
+    }).insert(CoinsText)
+      .insert(ShopEntity);
+
+    for index in 0..=shop.offers.len() {
+        commands.spawn_bundle(Text2dBundle {
+            text: Text::with_section("", text_style(&fonts.fira_sans, 30.), alignment),
+            transform: Transform {
+                translation: Vec3::new(0., 60. - 50. * (index as f32), 21.),
+                .. Default::default()
+            },
+            .. Default::default()
+        }).insert(ShopOption { index })
+          .insert(ShopEntity);
+    }
+}
+
+/// Move the highlight to the next option that's still for sale.
+fn cycle_selection(
+    mut shop: ResMut<Shop>,
+    time: Res<Time>,
+) {
+    let now = time.time_since_startup();
+    if now < shop.next_cycle {
+        return;
+    }
+    shop.next_cycle = now + CYCLE_PERIOD;
+
+    let num_options = shop.offers.len() + 1;
+    let mut next = shop.selected;
+    loop {
+        next = (next + 1) % num_options;
+        // "Continue" is never sold, so this terminates.
+        if next == shop.offers.len() || shop.offers[next].is_some() {
+            break;
+        }
+    }
+    shop.selected = next;
+}
+
+fn keyboard_input(
+    mut button: PrimaryButton,
+    mut healed_writer: EventWriter<Healed>,
+    mut player: Query<(Entity, &mut Health), With<Player>>,
+    audio: Res<Audio>,
+    mut coins: ResMut<Coins>,
+    mut replay_state: ResMut<ReplayState>,
+    mut shop: ResMut<Shop>,
+    sounds: Res<Sounds>,
+    mut state: ResMut<State<GameState>>,
+    mut upgrades: ResMut<Upgrades>,
+) {
+    // Take the press even during a replay, so it isn't seen once the replay ends.
+    let pressed = button.take();
+    if replay_state.is_playing() {
+        match replay_state.next_shop_pick() {
+            Some(pick) => shop.selected = pick,
+            // The recording ended here, carry on to the fight.
+            None => shop.selected = shop.offers.len(),
+        }
+    } else {
+        if !pressed {
+            return;
+        }
+        replay_state.record_shop_pick(shop.selected);
+    }
+
+    let selected = shop.selected;
+    if selected == shop.offers.len() {
+        state.pop().unwrap();
+        return;
+    }
+
+    let upgrade = match &shop.offers[selected] {
+        Some(u) => u.clone(),
+        None => return,
+    };
+    if upgrade.cost() > coins.0 {
+        audio.play(sounds.shield.clone());
+        return;
+    }
+
+    coins.0 -= upgrade.cost();
+    for (entity, mut health) in player.single_mut() {
+        upgrade.apply(&mut upgrades, &mut health);
+        if matches!(upgrade, Upgrade::MaxHp(_) | Upgrade::Heal(_)) {
+            healed_writer.send(Healed { target: entity });
+        }
+    }
+    debug!("shop: bought {:?}", upgrade);
+    audio.play(sounds.snare.clone());
+    shop.offers[selected] = None;
+}
+
+fn update_text(
+    mut options: Query<(&ShopOption, &mut Text), Without<CoinsText>>,
+    mut coins_text: Query<&mut Text, With<CoinsText>>,
+    coins: Res<Coins>,
+    shop: Res<Shop>,
+) {
+    for mut text in coins_text.single_mut() {
+        text.sections[0].value = format!("Coins: {}", coins.0);
+    }
+
+    for (option, mut text) in options.iter_mut() {
+        let section = &mut text.sections[0];
+        section.value = match shop.offers.get(option.index) {
+            None => "Continue".to_owned(),
+            Some(None) => "Sold".to_owned(),
+            Some(Some(upgrade)) =>
+                format!("{} - {} coins", upgrade.description(), upgrade.cost()),
+        };
+        section.style.color =
+            if option.index == shop.selected {
+                Color::rgb(242./255., 0., 48./255.)
+            } else if let Some(Some(upgrade)) = shop.offers.get(option.index) {
+                if upgrade.cost() > coins.0 {
+                    Color::GRAY
+                } else {
+                    Color::WHITE
+                }
+            } else {
+                Color::WHITE
+            };
+    }
+}
+
+fn cleanup(
+    mut commands: Commands,
+    query: Query<Entity, With<ShopEntity>>,
+) {
+    for ent in query.iter() {
+        commands
+            .entity(ent)
+            .despawn();
+    }
+}
+
+fn resume_music(
+    audio: Res<Audio>,
+    channels: Res<Channels>,
+) {
+    audio.resume_channel(&channels.music);
+}
@@ -0,0 +1,140 @@
+use bevy::prelude::*;
+use crate::{
+    campaign::Song,
+    game_state::GameState,
+    layout::{Anchor, Anchored},
+    loading::Fonts,
+    navigation::{self, Navigation},
+    resources::Mode,
+};
+
+/// Picks a song to practice. Practice runs play the chosen song throughout and the player
+/// can't die.
+pub struct Plugin;
+
+struct SongSelectEntity;
+
+struct SongOption {
+    /// Index into `Song::ALL`, or `Song::ALL.len()` for "Back".
+    index: usize,
+}
+
+struct SongSelect {
+    selected: usize,
+}
+
+const OPTIONS_Y: f32 = 100.;
+const OPTION_SPACING: f32 = 40.;
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .insert_resource(SongSelect { selected: 0 })
+            .add_system_set(
+                SystemSet::on_enter(GameState::SongSelect)
+                    .with_system(on_enter.system()))
+            .add_system_set(
+                SystemSet::on_update(GameState::SongSelect)
+                    .with_system(navigate.system())
+                    .with_system(update_text.system()))
+            .add_system_set(
+                SystemSet::on_exit(GameState::SongSelect)
+                    .with_system(cleanup.system()))
+            ;
+    }
+}
+
+fn on_enter(
+    mut commands: Commands,
+    fonts: Res<Fonts>,
+    mut song_select: ResMut<SongSelect>,
+) {
+    song_select.selected = 0;
+
+    let alignment = TextAlignment {
+        vertical: VerticalAlign::Center,
+        horizontal: HorizontalAlign::Center,
+    };
+
+    commands.spawn_bundle(Text2dBundle {
+        text: Text::with_section(
+            "Song Select",
+            TextStyle {
+                font: fonts.fiendish.clone(),
+                font_size: 60.,
+                color: Color::rgb(242./255., 0., 48./255.),
+            },
+            alignment),
+        transform: Transform {
+            translation: Vec3::new(0., 220., 10.),
+            .. Default::default()
+        },
+        .. Default::default()
+    }).insert(Anchored::new(Anchor::Top))
+      .insert(SongSelectEntity);
+
+    for index in 0..=Song::ALL.len() {
+        commands.spawn_bundle(Text2dBundle {
+            text: Text::with_section(
+                Song::ALL.get(index).map(|s| s.name()).unwrap_or("Back"),
+                TextStyle {
+                    font: fonts.fira_sans.clone(),
+                    font_size: 30.,
+                    color: Color::WHITE,
+                },
+                alignment),
+            transform: Transform {
+                translation: Vec3::new(0., OPTIONS_Y - OPTION_SPACING * index as f32, 10.),
+                .. Default::default()
+            },
+            .. Default::default()
+        }).insert(SongOption { index })
+          .insert(SongSelectEntity);
+    }
+}
+
+fn navigate(
+    mut nav: Navigation,
+    mut mode: ResMut<Mode>,
+    mut song_select: ResMut<SongSelect>,
+    mut state: ResMut<State<GameState>>,
+) {
+    let count = Song::ALL.len() + 1;
+    let chosen = nav.navigate(&mut song_select.selected, count, |point| {
+        navigation::option_in_list(point, OPTIONS_Y, OPTION_SPACING, count)
+    });
+    if !chosen {
+        return;
+    }
+
+    match Song::ALL.get(song_select.selected) {
+        Some(song) => {
+            *mode = Mode::Practice(*song);
+            state.set(GameState::Loadout).unwrap();
+        },
+        None => state.set(GameState::Menu).unwrap(),
+    }
+}
+
+fn update_text(
+    mut query: Query<(&SongOption, &mut Text)>,
+    song_select: Res<SongSelect>,
+) {
+    for (option, mut text) in query.iter_mut() {
+        text.sections[0].style.color =
+            if option.index == song_select.selected {
+                Color::rgb(242./255., 0., 48./255.)
+            } else {
+                Color::WHITE
+            };
+    }
+}
+
+fn cleanup(
+    mut commands: Commands,
+    query: Query<Entity, With<SongSelectEntity>>,
+) {
+    for ent in query.iter() {
+        commands.entity(ent).despawn();
+    }
+}
@@ -0,0 +1,368 @@
+use bevy::{
+    prelude::*,
+    window::WindowCloseRequested,
+};
+use crate::{
+    components::{Action, Character, Enemy, Player},
+    events::{ActionTimed, DamageApplied, DamageBlocked, Die, PlayerAttackAction, RunEnded},
+    game_state::GameState,
+    input::PrimaryButton,
+    layout::{Anchor, Anchored},
+    loading::Fonts,
+    replay,
+    save::Saves,
+    settings::Settings,
+    types::{DamageType, Hp},
+};
+use serde::{Deserialize, Serialize};
+
+/// Keeps lifetime totals across every run, saved when leaving a fight (including to suspend
+/// the run) and when the window is closed, and shown on the stats screen. Replays aren't
+/// counted.
+pub struct Plugin;
+
+const SAVE_KEY: &str = "stats";
+
+/// Missing fields take their default value, so older stats files still load.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Stats {
+    pub runs: u32,
+    pub wins: u32,
+    pub deaths: Vec<DeathStats>,
+    pub actions: Vec<ActionStats>,
+    pub attacks: Vec<AttackStats>,
+    pub damage_dealt: Hp,
+    pub damage_taken: Hp,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DeathStats {
+    pub killed_by: Character,
+    pub count: u32,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ActionStats {
+    pub action: Action,
+    pub hits: u32,
+    pub misses: u32,
+    /// Sum of the timing offsets of hits, negative is early.
+    pub hit_offset_secs: f64,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct AttackStats {
+    pub damage_type: DamageType,
+    pub count: u32,
+    /// Attacks the enemy wasn't vulnerable to.
+    pub resisted: u32,
+}
+
+struct StatsEntity;
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_system_set(
+                SystemSet::on_enter(GameState::CreateResources)
+                    .with_system(create_resources.system()))
+            .add_system(save_on_close.system())
+            .add_system_set(
+                replay::live_on_update()
+                    .with_system(action_timed.system())
+                    .with_system(player_attack.system())
+                    .with_system(damage_applied.system())
+                    .with_system(damage_blocked.system())
+                    .with_system(die.system()))
+            // RunEnded is sent just before leaving Playing.
+            .add_system_set(
+                replay::live_on_exit()
+                    .with_system(save_on_exit.system().before("replay_end")))
+            .add_system_set(
+                SystemSet::on_enter(GameState::Stats)
+                    .with_system(on_enter.system()))
+            .add_system_set(
+                SystemSet::on_update(GameState::Stats)
+                    .with_system(keyboard_input.system()))
+            .add_system_set(
+                SystemSet::on_exit(GameState::Stats)
+                    .with_system(cleanup.system()))
+            ;
+    }
+}
+
+impl Stats {
+    fn deaths_mut(&mut self, killed_by: &Character) -> &mut DeathStats {
+        match self.deaths.iter().position(|d| &d.killed_by == killed_by) {
+            Some(idx) => &mut self.deaths[idx],
+            None => {
+                self.deaths.push(DeathStats {
+                    killed_by: killed_by.clone(),
+                    count: 0,
+                });
+                self.deaths.last_mut().unwrap()
+            }
+        }
+    }
+
+    fn action_mut(&mut self, action: &Action) -> &mut ActionStats {
+        match self.actions.iter().position(|a| &a.action == action) {
+            Some(idx) => &mut self.actions[idx],
+            None => {
+                self.actions.push(ActionStats {
+                    action: action.clone(),
+                    hits: 0,
+                    misses: 0,
+                    hit_offset_secs: 0.,
+                });
+                self.actions.last_mut().unwrap()
+            }
+        }
+    }
+
+    fn attack_mut(&mut self, damage_type: &DamageType) -> &mut AttackStats {
+        match self.attacks.iter().position(|a| &a.damage_type == damage_type) {
+            Some(idx) => &mut self.attacks[idx],
+            None => {
+                self.attacks.push(AttackStats {
+                    damage_type: damage_type.clone(),
+                    count: 0,
+                    resisted: 0,
+                });
+                self.attacks.last_mut().unwrap()
+            }
+        }
+    }
+
+    /// Average timing offset in seconds over all hits, negative is early.
+    pub fn average_offset_secs(&self) -> Option<f64> {
+        let hits: u32 = self.actions.iter().map(|a| a.hits).sum();
+        if hits == 0 {
+            return None;
+        }
+        let total: f64 = self.actions.iter().map(|a| a.hit_offset_secs).sum();
+        Some(total / hits as f64)
+    }
+}
+
+fn create_resources(
+    mut commands: Commands,
+    saves: Res<Saves>,
+) {
+    let stats: Stats = saves.load(SAVE_KEY).unwrap_or_default();
+    commands.insert_resource(stats);
+}
+
+fn action_timed(
+    mut timed_reader: EventReader<ActionTimed>,
+    mut stats: ResMut<Stats>,
+) {
+    for timed in timed_reader.iter() {
+        let action_stats = stats.action_mut(&timed.action);
+        if timed.hit {
+            action_stats.hits += 1;
+            action_stats.hit_offset_secs += timed.offset_secs;
+        } else {
+            action_stats.misses += 1;
+        }
+    }
+}
+
+fn player_attack(
+    mut attack_reader: EventReader<PlayerAttackAction>,
+    mut stats: ResMut<Stats>,
+) {
+    for attack in attack_reader.iter() {
+        stats.attack_mut(&attack.damage_type).count += 1;
+    }
+}
+
+fn damage_applied(
+    mut damage_applied_reader: EventReader<DamageApplied>,
+    enemy_query: Query<&Enemy>,
+    player_query: Query<&Player>,
+    mut stats: ResMut<Stats>,
+) {
+    for damage_applied in damage_applied_reader.iter() {
+        let damage = &damage_applied.damage;
+        if enemy_query.get(damage.target).is_ok() {
+            stats.damage_dealt += damage.hp;
+        } else if player_query.get(damage.target).is_ok() {
+            stats.damage_taken += damage.hp;
+        }
+    }
+}
+
+fn damage_blocked(
+    mut damage_blocked_reader: EventReader<DamageBlocked>,
+    enemy_query: Query<&Enemy>,
+    mut stats: ResMut<Stats>,
+) {
+    for blocked in damage_blocked_reader.iter() {
+        if enemy_query.get(blocked.damage.target).is_ok() {
+            stats.attack_mut(&blocked.damage.damage_type).resisted += 1;
+        }
+    }
+}
+
+fn die(
+    mut die_reader: EventReader<Die>,
+    enemy_query: Query<&Character, With<Enemy>>,
+    player_query: Query<&Player>,
+    mut stats: ResMut<Stats>,
+) {
+    for die in die_reader.iter() {
+        if player_query.get(die.target).is_err() {
+            continue;
+        }
+        // There's only one enemy at a time, and it's still alive if the player died.
+        if let Ok(character) = enemy_query.single() {
+            stats.deaths_mut(character).count += 1;
+        }
+    }
+}
+
+/// Leaving a fight either ends the run or suspends it.
+fn save_on_exit(
+    mut run_ended_reader: EventReader<RunEnded>,
+    saves: Res<Saves>,
+    mut stats: ResMut<Stats>,
+) {
+    for run_ended in run_ended_reader.iter() {
+        stats.runs += 1;
+        if run_ended.won {
+            stats.wins += 1;
+        }
+    }
+    saves.save(SAVE_KEY, &*stats);
+}
+
+/// The window can close from any state, including while paused or in the shop.
+fn save_on_close(
+    mut close_reader: EventReader<WindowCloseRequested>,
+    saves: Res<Saves>,
+    stats: Option<Res<Stats>>,
+) {
+    if close_reader.iter().next().is_none() {
+        return;
+    }
+    // Not created until loading has finished.
+    if let Some(stats) = stats {
+        saves.save(SAVE_KEY, &*stats);
+    }
+}
+
+fn stats_lines(stats: &Stats) -> Vec<String> {
+    let mut lines = vec![format!("Runs: {}   Wins: {}", stats.runs, stats.wins)];
+
+    if !stats.deaths.is_empty() {
+        let deaths: Vec<String> = stats.deaths.iter()
+            .map(|d| format!("{:?} {}", d.killed_by, d.count))
+            .collect();
+        lines.push(format!("Killed by: {}", deaths.join(", ")));
+    }
+
+    for a in stats.actions.iter() {
+        let presses = a.hits + a.misses;
+        lines.push(format!("{}: {} of {} on time ({}%)",
+                           a.action.name(), a.hits, presses, a.hits * 100 / presses.max(1)));
+    }
+
+    if let Some(offset) = stats.average_offset_secs() {
+        let ms = (offset * 1000.).round() as i64;
+        lines.push(format!("Average timing: {} ms {}",
+                           ms.abs(), if ms < 0 { "early" } else { "late" }));
+    }
+
+    for a in stats.attacks.iter() {
+        lines.push(format!("{:?} attacks: {}, resisted {}", a.damage_type, a.count, a.resisted));
+    }
+
+    lines.push(format!("Damage dealt: {}   Damage taken: {}",
+                       stats.damage_dealt, stats.damage_taken));
+    lines
+}
+
+fn on_enter(
+    mut commands: Commands,
+    fonts: Res<Fonts>,
+    settings: Res<Settings>,
+    stats: Res<Stats>,
+) {
+    let alignment = TextAlignment {
+        vertical: VerticalAlign::Center,
+        horizontal: HorizontalAlign::Center,
+    };
+
+    commands.spawn_bundle(Text2dBundle {
+        text: Text::with_section(
+            "Stats",
+            TextStyle {
+                font: fonts.fiendish.clone(),
+                font_size: 60.,
+                color: Color::rgb(242./255., 0., 48./255.),
+            },
+            alignment),
+        transform: Transform {
+            translation: Vec3::new(0., 240., 10.),
+            .. Default::default()
+        },
+        .. Default::default()
+    }).insert(Anchored::new(Anchor::Top))
+      .insert(StatsEntity);
+
+    commands.spawn_bundle(Text2dBundle {
+        text: Text::with_section(
+            stats_lines(&stats).join("\n"),
+            TextStyle {
+                font: fonts.fira_sans.clone(),
+                font_size: 20.,
+                color: Color::WHITE,
+            },
+            TextAlignment {
+                vertical: VerticalAlign::Top,
+                horizontal: HorizontalAlign::Center,
+            }),
+        transform: Transform {
+            translation: Vec3::new(0., 180., 10.),
+            .. Default::default()
+        },
+        .. Default::default()
+    }).insert(StatsEntity);
+
+    commands.spawn_bundle(Text2dBundle {
+        text: Text::with_section(
+            settings.keys.prompt("return"),
+            TextStyle {
+                font: fonts.fira_sans.clone(),
+                font_size: 20.,
+                color: Color::WHITE,
+            },
+            alignment),
+        transform: Transform {
+            translation: Vec3::new(0., -250., 10.),
+            .. Default::default()
+        },
+        .. Default::default()
+    }).insert(Anchored::new(Anchor::Bottom))
+      .insert(StatsEntity);
+}
+
+fn keyboard_input(
+    mut button: PrimaryButton,
+    mut state: ResMut<State<GameState>>,
+) {
+    if button.take() {
+        state.set(GameState::Menu).unwrap();
+    }
+}
+
+fn cleanup(
+    mut commands: Commands,
+    query: Query<Entity, With<StatsEntity>>,
+) {
+    for ent in query.iter() {
+        commands.entity(ent).despawn();
+    }
+}
@@ -0,0 +1,182 @@
+//! Saves a run in progress so it can be resumed from the menu later.
+//!
+//! The run is saved at the start of each fight, when the window is closed, and when the
+//! player presses the suspend key. A run that ends removes its save. The menu shows a Resume
+//! item while there's a saved run.
+
+use bevy::{
+    prelude::*,
+    window::WindowCloseRequested,
+};
+use crate::{
+    components::{Character, Enemy, Health, Player},
+    events::RunEnded,
+    game_state::GameState,
+    replay::ReplayState,
+    resources::{Checkpoint, Coins, Difficulty, Level, Loadout, Mode, Resumed, RunRng, RunStart,
+                Score, Upgrades},
+    save::Saves,
+    settings::Settings,
+    types::Hp,
+};
+use serde::{Deserialize, Serialize};
+
+pub struct Plugin;
+
+const SAVE_KEY: &str = "suspended_run";
+
+#[derive(Deserialize, Serialize)]
+pub struct SuspendedRun {
+    pub level: Level,
+    pub checkpoint: Checkpoint,
+    pub seed: u64,
+    /// `RunRng::draws`, so the run carries on the same random sequence.
+    #[serde(default)]
+    pub draws: u64,
+    pub score: Score,
+    pub upgrades: Upgrades,
+    pub coins: Coins,
+    pub loadout: Loadout,
+    pub difficulty: Difficulty,
+    #[serde(default)]
+    pub mode: Mode,
+    pub player_hp: Hp,
+    /// `None` if the enemy was already dead, then a new one is chosen on resume.
+    pub enemy: Option<(Character, Hp)>,
+}
+
+/// Sent by the menu's Resume item.
+pub struct ResumeRequested;
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_event::<ResumeRequested>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Menu)
+                    .with_system(resume.system()))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(save_run.system()))
+            // RunEnded is sent just before leaving Playing.
+            .add_system_set(
+                SystemSet::on_exit(GameState::Playing)
+                    .with_system(run_ended.system()))
+            ;
+    }
+}
+
+pub fn can_resume(saves: &Saves) -> bool {
+    saves.load::<SuspendedRun>(SAVE_KEY).is_some()
+}
+
+fn resume(
+    mut resume_reader: EventReader<ResumeRequested>,
+    mut checkpoint: ResMut<Checkpoint>,
+    mut coins: ResMut<Coins>,
+    mut difficulty: ResMut<Difficulty>,
+    mut level: ResMut<Level>,
+    mut loadout: ResMut<Loadout>,
+    mut mode: ResMut<Mode>,
+    mut resumed: ResMut<Resumed>,
+    mut run_rng: ResMut<RunRng>,
+    mut run_start: ResMut<RunStart>,
+    saves: Res<Saves>,
+    mut score: ResMut<Score>,
+    mut state: ResMut<State<GameState>>,
+    mut upgrades: ResMut<Upgrades>,
+) {
+    if resume_reader.iter().next().is_none() {
+        return;
+    }
+
+    let run: SuspendedRun = match saves.load(SAVE_KEY) {
+        Some(r) => r,
+        None => return,
+    };
+
+    *level = run.level;
+    *checkpoint = run.checkpoint;
+    run_rng.seed = run.seed;
+    run_rng.draws = run.draws;
+    *score = run.score;
+    *upgrades = run.upgrades;
+    *coins = run.coins;
+    *loadout = run.loadout;
+    *difficulty = run.difficulty;
+    *mode = run.mode;
+    *resumed = Resumed {
+        player_hp: Some(run.player_hp),
+        enemy: run.enemy,
+    };
+    *run_start = RunStart::Resume;
+    state.set(GameState::Playing).unwrap();
+}
+
+fn save_run(
+    mut close_reader: EventReader<WindowCloseRequested>,
+    mut kb: ResMut<Input<KeyCode>>,
+    new_enemy_query: Query<(), Added<Enemy>>,
+    enemy_query: Query<(&Character, &Health), With<Enemy>>,
+    player_query: Query<&Health, With<Player>>,
+    checkpoint: Res<Checkpoint>,
+    coins: Res<Coins>,
+    difficulty: Res<Difficulty>,
+    level: Res<Level>,
+    loadout: Res<Loadout>,
+    mode: Res<Mode>,
+    replay_state: Res<ReplayState>,
+    run_rng: Res<RunRng>,
+    saves: Res<Saves>,
+    score: Res<Score>,
+    settings: Res<Settings>,
+    mut state: ResMut<State<GameState>>,
+    upgrades: Res<Upgrades>,
+) {
+    let suspend = kb.just_pressed(settings.keys.suspend);
+    let closing = close_reader.iter().next().is_some();
+    // A fight starts once its enemy has spawned, which is the frame after the level changes.
+    // After a new stage's shop, this includes what was bought.
+    let new_fight = new_enemy_query.iter().next().is_some();
+    if replay_state.is_playing() || !(suspend || closing || new_fight) {
+        return;
+    }
+
+    let player_hp = match player_query.single() {
+        Ok(health) if health.current > 0 => health.current,
+        // Dying, let the run end normally.
+        _ => return,
+    };
+
+    let run = SuspendedRun {
+        level: level.clone(),
+        checkpoint: checkpoint.clone(),
+        seed: run_rng.seed,
+        draws: run_rng.draws,
+        score: score.clone(),
+        upgrades: upgrades.clone(),
+        coins: *coins,
+        loadout: loadout.clone(),
+        difficulty: *difficulty,
+        mode: *mode,
+        player_hp,
+        enemy: enemy_query.single().ok()
+            .filter(|(_, health)| health.current > 0)
+            .map(|(character, health)| (character.clone(), health.current)),
+    };
+    saves.save(SAVE_KEY, &run);
+
+    if suspend {
+        kb.reset(settings.keys.suspend);
+        state.set(GameState::Menu).unwrap();
+    }
+}
+
+fn run_ended(
+    mut run_ended_reader: EventReader<RunEnded>,
+    saves: Res<Saves>,
+) {
+    if run_ended_reader.iter().next().is_some() {
+        saves.remove(SAVE_KEY);
+    }
+}
@@ -1,17 +1,35 @@
 use bevy::prelude::*;
 use crate::{
-    components::AnimateSpriteSheet,
+    components::{AnimateSpriteSheet, Flashing},
     game_state::GameState,
+    settings::Settings,
 };
 
 pub struct Plugin;
 
+/// Opacity of a flashing effect when reduced flashing is on.
+pub const REDUCED_FLASHING_ALPHA: f32 = 0.4;
+
 impl bevy::app::Plugin for Plugin {
     fn build(&self, app: &mut AppBuilder) {
         app
             .add_system_set(
                 SystemSet::on_update(GameState::Playing)
-                    .with_system(animate.system()));
+                    .with_system(reduce_flashing.system().before("animate"))
+                    .with_system(animate.system().label("animate")));
+    }
+}
+
+fn reduce_flashing(
+    mut query: Query<(&mut TextureAtlasSprite, &mut AnimateSpriteSheet), Added<Flashing>>,
+    settings: Res<Settings>,
+) {
+    if !settings.accessibility.reduced_flashing {
+        return;
+    }
+    for (mut sprite, mut anim) in query.iter_mut() {
+        *anim = AnimateSpriteSheet::never();
+        sprite.color.set_a(REDUCED_FLASHING_ALPHA);
     }
 }
 
@@ -4,22 +4,37 @@ use crate::{
     game_state::GameState,
     loading::{CountdownTextures, Sounds},
     resources::Countdown,
+    settings::Settings,
+    systems::animate_sprite_sheet::REDUCED_FLASHING_ALPHA,
 };
 use std::time::Duration;
 
 pub struct Plugin;
 
+/// From the countdown starting to the fight resuming.
+pub const LENGTH: Duration = Duration::from_secs(4);
+
+/// Wait before the next countdown starts, e.g. to line it up with the music after a pause.
+#[derive(Default)]
+pub struct CountdownDelay(pub Duration);
+
 struct CountdownSprite {
     number: u8,
     next_count: Duration,
+    /// Hidden and silent until then, if the countdown was delayed.
+    start_at: Option<Duration>,
 }
 
 impl bevy::app::Plugin for Plugin {
     fn build(&self, app: &mut AppBuilder) {
         app
+            .insert_resource(CountdownDelay::default())
             .add_system_set(
                 SystemSet::on_enter(GameState::Playing)
                     .with_system(setup.system()))
+            .add_system_set(
+                SystemSet::on_resume(GameState::Playing)
+                    .with_system(setup.system()))
             .add_system_set(
                 SystemSet::on_update(GameState::Playing)
                     .with_system(show_countdown.system()))
@@ -29,13 +44,39 @@ impl bevy::app::Plugin for Plugin {
 
 fn setup(
     mut commands: Commands,
+    existing_query: Query<Entity, With<CountdownSprite>>,
     tex: Res<CountdownTextures>,
     audio: Res<Audio>,
+    mut delay: ResMut<CountdownDelay>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    settings: Res<Settings>,
     sounds: Res<Sounds>,
     time: Res<Time>,
 ) {
+    // The numbers flash up in the middle of the screen, so fade them with reduced flashing.
+    let alpha =
+        if settings.accessibility.reduced_flashing {
+            REDUCED_FLASHING_ALPHA
+        } else {
+            1.
+        };
+    for handle in [&tex.number_3, &tex.number_2, &tex.number_1, &tex.fight].iter() {
+        if let Some(material) = materials.get_mut(*handle) {
+            material.color.set_a(alpha);
+        }
+    }
+
+    // A countdown interrupted by a pause starts again.
+    for ent in existing_query.iter() {
+        commands.entity(ent).despawn();
+    }
+
     commands.insert_resource(Countdown::Counting);
 
+    let start = time.time_since_startup() + delay.0;
+    let delayed = delay.0 > Duration::default();
+    delay.0 = Duration::default();
+
     commands.spawn_bundle(SpriteBundle {
         material: tex.number_3.clone(),
         transform: Transform {
@@ -43,24 +84,41 @@ fn setup(
             scale: Vec3::ONE,
             .. Default::default()
         },
+        visible: Visible {
+            is_visible: !delayed,
+            is_transparent: true,
+        },
         .. Default::default()
     }).insert(CountdownSprite {
         number: 3,
-        next_count: time.time_since_startup() + Duration::from_secs(1),
+        next_count: start + Duration::from_secs(1),
+        start_at: if delayed { Some(start) } else { None },
     });
 
-    audio.play(sounds.countdown.clone());
+    if !delayed {
+        audio.play(sounds.countdown.clone());
+    }
 }
 
 fn show_countdown(
     mut commands: Commands,
-    mut sprite: Query<(Entity, &mut Handle<ColorMaterial>, &mut CountdownSprite)>,
+    mut sprite: Query<(Entity, &mut Handle<ColorMaterial>, &mut Visible, &mut CountdownSprite)>,
+    audio: Res<Audio>,
     mut countdown_res: ResMut<Countdown>,
+    sounds: Res<Sounds>,
     tex: Res<CountdownTextures>,
     time: Res<Time>,
 ) {
-    for (entity, mut mat, mut count) in sprite.single_mut() {
+    for (entity, mut mat, mut visible, mut count) in sprite.single_mut() {
         let now = time.time_since_startup();
+        if let Some(start) = count.start_at {
+            if now < start {
+                return;
+            }
+            count.start_at = None;
+            visible.is_visible = true;
+            audio.play(sounds.countdown.clone());
+        }
         if now > count.next_count {
             let next_number = count.number.checked_sub(1);
             match next_number {
@@ -1,55 +1,39 @@
 use bevy::prelude::*;
 use bevy_kira_audio::Audio;
 use crate::{
-    components::{DespawnAfter, Health},
-    events::{Die, Damage, DamageApplied},
+    components::{Health, Player},
+    events::{Die, Damage, DamageApplied, DamageBlocked},
+    floating_text::{self, FloatingTexts},
     game_state::GameState,
-    loading::{self, Sounds},
+    loading::Sounds,
+    resources::Mode,
 };
-use std::time::Duration;
 
-struct DamageSprites {
-    hit: Handle<ColorMaterial>,
-    blocked: Handle<ColorMaterial>,
-}
-
-struct DamageDisplay;
+/// Floating text starts this far above the target's centre.
+const TEXT_OFFSET_Y: f32 = 50.;
 
 pub struct Plugin;
 
 impl bevy::app::Plugin for Plugin {
     fn build(&self, app: &mut AppBuilder) {
         app
-            .add_system_set(
-                SystemSet::on_enter(GameState::Setup)
-                    .with_system(create_resources.system()))
             .add_system_set(
                 SystemSet::on_update(GameState::Playing)
                     .with_system(process_damage.system()));
     }
 }
 
-fn create_resources(
-    mut commands: Commands,
-    texture_assets: Res<loading::TextureAssets>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-) {
-    commands.insert_resource(DamageSprites {
-        hit: materials.add(texture_assets.damage_hit.clone().into()),
-        blocked: materials.add(texture_assets.damage_blocked.clone().into()),
-    });
-}
-
 fn process_damage(
-    mut commands: Commands,
     mut damage_reader: EventReader<Damage>,
     mut damage_applied_writer: EventWriter<DamageApplied>,
+    mut damage_blocked_writer: EventWriter<DamageBlocked>,
     mut die_writer: EventWriter<Die>,
+    mut floating_texts: FloatingTexts,
     mut health_query: Query<(&mut Health, &Transform)>,
+    player_query: Query<&Player>,
     audio: Res<Audio>,
-    sprites: Res<DamageSprites>,
+    mode: Res<Mode>,
     sounds: Res<Sounds>,
-    time: Res<Time>,
 ) {
     for damage in damage_reader.iter() {
         let (mut health, health_transform) = match health_query.get_mut(damage.target) {
@@ -59,6 +43,8 @@ fn process_damage(
             },
             Ok(h) => h,
         };
+        let text_position = health_transform.translation + Vec3::new(0., TEXT_OFFSET_Y, 5.);
+        let color = floating_text::damage_color(&damage.damage_type);
         if health.vulnerable_to.contains(&damage.damage_type) {
             damage_applied_writer.send(DamageApplied {
                 damage: damage.clone(),
@@ -66,40 +52,30 @@ fn process_damage(
 
             // Vulnerable to damage
             health.current = health.current.checked_sub(damage.hp).unwrap_or(0);
+            if let Mode::Practice(_) = *mode {
+                if player_query.get(damage.target).is_ok() {
+                    // The player can't die in practice.
+                    health.current = health.current.max(1);
+                }
+            }
             if health.current == 0 {
                 die_writer.send(Die {
                     target: damage.target,
                 });
             }
 
-            commands.spawn_bundle(SpriteBundle {
-                material: sprites.hit.clone(),
-                transform: Transform {
-                    translation: health_transform.translation + Vec3::new(150., 0., 0.),
-                    scale: Vec3::ONE * 0.75,
-                    .. Default::default()
-                },
-                .. Default::default()
-            })
-                .insert(DamageDisplay)
-                .insert(DespawnAfter {
-                    after: time.time_since_startup() + Duration::from_millis(300),
-                });
+            floating_texts.spawn(&damage.hp.to_string(), color, 40., text_position);
+            if damage.crit {
+                floating_texts.spawn("CRIT", Color::rgb(242./255., 0., 48./255.), 30.,
+                                     text_position + Vec3::new(0., 35., 0.));
+            }
         } else {
             // Not vulnerable to damage.
-            commands.spawn_bundle(SpriteBundle {
-                material: sprites.blocked.clone(),
-                transform: Transform {
-                    translation: health_transform.translation + Vec3::new(150., 0., 0.),
-                    scale: Vec3::ONE * 0.5,
-                    .. Default::default()
-                },
-                .. Default::default()
-            })
-                .insert(DamageDisplay)
-                .insert(DespawnAfter {
-                    after: time.time_since_startup() + Duration::from_millis(300),
-                });
+            damage_blocked_writer.send(DamageBlocked {
+                damage: damage.clone(),
+            });
+
+            floating_texts.spawn("BLOCKED", color, 30., text_position);
 
             audio.play(sounds.shield.clone());
         }
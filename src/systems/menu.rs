@@ -1,23 +1,64 @@
-use bevy::prelude::*;
+use bevy::{
+    app::AppExit,
+    prelude::*,
+};
 use bevy_kira_audio::Audio;
 use crate::{
+    campaign::Song,
     game_state::GameState,
-    loading::{self, Sounds},
+    layout::{Anchor, Anchored},
+    loading::{self, Fonts, Sounds},
+    music::Channels,
+    navigation::{self, Navigation},
+    replay::{self, ReplayRequested},
+    resources::{Difficulty, Mode},
+    save::Saves,
+    settings::Settings,
+    suspend::{self, ResumeRequested},
 };
 
 struct Menu;
 
 struct Sprites {
     text: Handle<ColorMaterial>,
-    click_here: Handle<ColorMaterial>,
 }
 
-/// Should have a menu but currently just goes directly to playing
+#[derive(Clone, Copy, Debug)]
+enum MenuItem {
+    Resume,
+    Play,
+    Practice,
+    SongSelect,
+    Options,
+    Stats,
+    Bestiary,
+    Replay,
+    Credits,
+    Quit,
+}
+
+struct MenuOption {
+    index: usize,
+}
+
+struct MainMenu {
+    items: Vec<MenuItem>,
+    selected: usize,
+}
+
+const OPTIONS_Y: f32 = 55.;
+const OPTION_SPACING: f32 = 30.;
+
+/// The main menu, navigated with the one button, the arrow keys or the mouse.
 pub struct Plugin;
 
 impl bevy::app::Plugin for Plugin {
     fn build(&self, app: &mut AppBuilder) {
         app
+            .insert_resource(MainMenu {
+                items: Vec::new(),
+                selected: 0,
+            })
             .add_system_set(
                 SystemSet::on_enter(GameState::Setup)
                     .with_system(create_resources.system()))
@@ -26,7 +67,8 @@ impl bevy::app::Plugin for Plugin {
                     .with_system(on_enter.system()))
             .add_system_set(
                 SystemSet::on_update(GameState::Menu)
-                    .with_system(keyboard_input.system()))
+                    .with_system(navigate.system())
+                    .with_system(update_text.system()))
             .add_system_set(
                 SystemSet::on_exit(GameState::Menu)
                     .with_system(cleanup.system()))
@@ -34,6 +76,49 @@ impl bevy::app::Plugin for Plugin {
     }
 }
 
+impl MenuItem {
+    /// Resume is first, so it's selected, when there's a suspended run. Replay is only
+    /// offered once a run has been recorded.
+    fn all(can_resume: bool, can_replay: bool) -> Vec<MenuItem> {
+        let mut items = Vec::new();
+        if can_resume {
+            items.push(MenuItem::Resume);
+        }
+        items.extend_from_slice(&[
+            MenuItem::Play,
+            MenuItem::Practice,
+            MenuItem::SongSelect,
+            MenuItem::Options,
+            MenuItem::Stats,
+            MenuItem::Bestiary,
+        ]);
+        if can_replay {
+            items.push(MenuItem::Replay);
+        }
+        items.push(MenuItem::Credits);
+        // A browser tab can't be closed from the game.
+        if cfg!(not(target_arch = "wasm32")) {
+            items.push(MenuItem::Quit);
+        }
+        items
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            MenuItem::Resume => "Resume",
+            MenuItem::Play => "Play",
+            MenuItem::Practice => "Practice",
+            MenuItem::SongSelect => "Song Select",
+            MenuItem::Options => "Options",
+            MenuItem::Stats => "Stats",
+            MenuItem::Bestiary => "Bestiary",
+            MenuItem::Replay => "Replay",
+            MenuItem::Credits => "Credits",
+            MenuItem::Quit => "Quit",
+        }
+    }
+}
+
 fn create_resources(
     mut commands: Commands,
     mut materials: ResMut<Assets<ColorMaterial>>,
@@ -41,7 +126,6 @@ fn create_resources(
 ) {
     commands.insert_resource(Sprites {
         text: materials.add(texture_assets.menu_text.clone().into()),
-        click_here: materials.add(texture_assets.menu_click_here.clone().into()),
     });
 }
 
@@ -49,9 +133,20 @@ fn on_enter(
     mut commands: Commands,
     despawn_query: Query<Entity>,
     audio: Res<Audio>,
+    channels: Res<Channels>,
+    mut difficulty: ResMut<Difficulty>,
+    fonts: Res<Fonts>,
+    mut main_menu: ResMut<MainMenu>,
+    saves: Res<Saves>,
+    settings: Res<Settings>,
     sounds: Res<Sounds>,
     sprites: Res<Sprites>,
 ) {
+    main_menu.items = MenuItem::all(suspend::can_resume(&saves), replay::can_replay(&saves));
+    main_menu.selected = 0;
+    // A replay or resumed run may have left its own difficulty.
+    *difficulty = settings.gameplay.difficulty;
+
     for entity in despawn_query.iter() {
         commands.entity(entity).despawn();
     }
@@ -62,38 +157,118 @@ fn on_enter(
         .insert_bundle(SpriteBundle {
             material: sprites.text.clone(),
             transform: Transform {
-                translation: Vec3::new(0., 0., 10.),
-                scale: Vec3::ONE * 1.5,
+                translation: Vec3::new(0., 130., 10.),
+                scale: Vec3::ONE * 0.6,
                 .. Default::default()
             },
             .. Default::default()
         });
 
+    for (index, item) in main_menu.items.iter().enumerate() {
+        commands
+            .spawn()
+            .insert(Menu)
+            .insert(MenuOption { index })
+            .insert_bundle(Text2dBundle {
+                text: Text::with_section(
+                    item.label(),
+                    TextStyle {
+                        font: fonts.fira_sans.clone(),
+                        font_size: 28.,
+                        color: Color::WHITE,
+                    },
+                    TextAlignment {
+                        vertical: VerticalAlign::Center,
+                        horizontal: HorizontalAlign::Center,
+                    }),
+                transform: Transform {
+                    translation: Vec3::new(0., OPTIONS_Y - OPTION_SPACING * index as f32, 10.),
+                    .. Default::default()
+                },
+                .. Default::default()
+            });
+    }
+
     commands
         .spawn()
         .insert(Menu)
-        .insert_bundle(SpriteBundle {
-            material: sprites.click_here.clone(),
+        .insert(Anchored::new(Anchor::Bottom))
+        .insert_bundle(Text2dBundle {
+            text: Text::with_section(
+                "Tap to move, hold to choose",
+                TextStyle {
+                    font: fonts.fira_sans.clone(),
+                    font_size: 20.,
+                    color: Color::WHITE,
+                },
+                TextAlignment {
+                    vertical: VerticalAlign::Center,
+                    horizontal: HorizontalAlign::Center,
+                }),
             transform: Transform {
-                translation: Vec3::new(0., -200., 10.),
-                scale: Vec3::ONE * 0.7,
+                translation: Vec3::new(0., -260., 10.),
                 .. Default::default()
             },
             .. Default::default()
         });
 
-    audio.play_looped(sounds.main_menu_loop.clone());
+    audio.play_looped_in_channel(sounds.main_menu_loop.clone(), &channels.music);
 }
 
-fn keyboard_input(
-    mut kb: ResMut<Input<KeyCode>>,
+fn navigate(
+    mut nav: Navigation,
+    mut exit_writer: EventWriter<AppExit>,
+    mut replay_writer: EventWriter<ReplayRequested>,
+    mut resume_writer: EventWriter<ResumeRequested>,
+    mut main_menu: ResMut<MainMenu>,
+    mut mode: ResMut<Mode>,
     mut state: ResMut<State<GameState>>,
 ) {
-    if kb.just_pressed(KeyCode::Space) {
-        log::debug!("kb.just_pressed(Space)");
-        // .reset() space so Playing state doesn't think it's already pressed
-        kb.reset(KeyCode::Space);
-        state.set(GameState::Playing).unwrap();
+    let main_menu = &mut *main_menu;
+    let count = main_menu.items.len();
+    let chosen = nav.navigate(&mut main_menu.selected, count, |point| {
+        navigation::option_in_list(point, OPTIONS_Y, OPTION_SPACING, count)
+    });
+    if !chosen {
+        return;
+    }
+
+    let item = main_menu.items[main_menu.selected];
+    log::debug!("Menu item chosen: {:?}", item);
+    match item {
+        MenuItem::Resume => resume_writer.send(ResumeRequested),
+        MenuItem::Play => {
+            *mode = Mode::Campaign;
+            state.set(GameState::Loadout).unwrap();
+        },
+        MenuItem::Practice => {
+            // Keep the song from the last practice run.
+            if *mode == Mode::Campaign {
+                *mode = Mode::Practice(Song::ALL[0]);
+            }
+            state.set(GameState::Loadout).unwrap();
+        },
+        MenuItem::SongSelect => state.set(GameState::SongSelect).unwrap(),
+        MenuItem::Options => state.set(GameState::Options).unwrap(),
+        MenuItem::Stats => state.set(GameState::Stats).unwrap(),
+        MenuItem::Bestiary => state.set(GameState::Bestiary).unwrap(),
+        MenuItem::Replay => replay_writer.send(ReplayRequested),
+        MenuItem::Credits => state.set(GameState::Credits).unwrap(),
+        MenuItem::Quit => exit_writer.send(AppExit),
+    }
+}
+
+fn update_text(
+    mut query: Query<(&MenuOption, &mut Text)>,
+    main_menu: Res<MainMenu>,
+) {
+    for (option, mut text) in query.iter_mut() {
+        text.sections[0].style.color =
+            if option.index == main_menu.selected {
+                Color::rgb(242./255., 0., 48./255.)
+            } else {
+                Color::WHITE
+            };
     }
 }
 
@@ -101,6 +276,7 @@ fn cleanup(
     mut commands: Commands,
     query: Query<Entity, With<Menu>>,
     audio: Res<Audio>,
+    channels: Res<Channels>,
 ) {
     for ent in query.iter() {
         commands
@@ -109,4 +285,5 @@ fn cleanup(
     }
 
     audio.stop();
+    audio.stop_channel(&channels.music);
 }
@@ -1,9 +1,43 @@
+use bevy::prelude::Color;
+use serde::{Deserialize, Serialize};
+
 pub type Hp = u32;
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub enum DamageType {
     Arrow,
     Magic,
     Sword,
+    /// The boss's attack.
     Ray,
+    /// The player's Double Arrow.
+    Pierce,
+    /// The player's Ray.
+    Light,
+}
+
+/// How well a press of the button was timed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Judgement {
+    Perfect,
+    Good,
+    Miss,
+}
+
+impl Judgement {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Judgement::Perfect => "Perfect",
+            Judgement::Good => "Good",
+            Judgement::Miss => "Miss",
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            Judgement::Perfect => Color::rgb(242./255., 0., 48./255.),
+            Judgement::Good => Color::WHITE,
+            Judgement::Miss => Color::GRAY,
+        }
+    }
 }